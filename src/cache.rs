@@ -0,0 +1,387 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{read_to_string, Metadata},
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::UNIX_EPOCH,
+};
+
+use crate::hash::HashType;
+
+/// Default cache filename, placed in the root of each hashed directory.
+/// Hidden, so that it is not itself hashed unless `--include-hidden` is set.
+pub const CACHE_FILENAME: &str = ".chksumcache";
+
+const CACHE_HEADER: &str = "RELATIVEPATH\tSIZE\tMODIFIED\tINODE\tCORRUPT";
+
+/// File metadata used to decide whether a cached hash is still valid.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileMeta {
+    /// Size in bytes.
+    pub size: u64,
+    /// Modification time in nanoseconds since the Unix epoch.
+    pub modified: u128,
+    /// Inode number. Always 0 on non-unix platforms.
+    pub inode: u64,
+}
+
+impl FileMeta {
+    /// Returns `None` if metadata can not be read
+    /// or the modification time is before the Unix epoch.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let meta = path.metadata().ok()?;
+        let modified = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?.as_nanos();
+        Some(Self {
+            size: meta.len(),
+            modified,
+            inode: inode(&meta),
+        })
+    }
+}
+
+#[cfg(unix)]
+fn inode(meta: &Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    meta.ino()
+}
+
+#[cfg(not(unix))]
+fn inode(_meta: &Metadata) -> u64 {
+    0
+}
+
+#[derive(Debug, Clone)]
+struct CacheEntry {
+    meta: FileMeta,
+    /// One hash per hash type in `HashCache::hash_types`,
+    /// `None` if not yet calculated for this file.
+    hashes: Vec<Option<String>>,
+    /// A new hash differed from the cached one with unchanged metadata.
+    /// Cached hashes are then kept, but never used, so that the file
+    /// is re-hashed and reported again until it is restored or modified.
+    corrupt: bool,
+}
+
+/// On-disk hash cache for a single directory tree.
+/// Entries are keyed on path relative to the tree root
+/// and are only used if size, modification time and inode are unchanged.
+///
+/// Stored as a tab-separated file:
+/// `RELATIVEPATH\tSIZE\tMODIFIED\tINODE\tCORRUPT\t<HASHTYPE>[\t<HASHTYPE>...]`,
+/// with `CORRUPT` either `0` or `1`.
+#[derive(Debug)]
+pub struct HashCache {
+    /// Cache file.
+    path: PathBuf,
+    /// Root of the hashed directory tree.
+    root: PathBuf,
+    hash_types: Vec<HashType>,
+    entries: HashMap<PathBuf, CacheEntry>,
+    /// Relative paths looked up or inserted during this run.
+    seen: HashSet<PathBuf>,
+    /// Ignore cached hashes, but still update the cache.
+    rehash: bool,
+    pub hits: usize,
+    pub misses: usize,
+    /// Files with unchanged metadata, but a hash that differs from the cached one.
+    pub corrupt: Vec<PathBuf>,
+    /// The cache file exists, but could not be parsed and was ignored.
+    /// It is overwritten on save.
+    pub invalid: bool,
+}
+
+impl HashCache {
+    /// Opens the cache for directory `root`. The cache file is `root/.chksumcache`,
+    /// or, if `cache_dir` is set, a file in `cache_dir` named after the canonical path of `root`.
+    ///
+    /// A missing or unreadable cache file results in an empty cache.
+    /// An invalid cache file also does, but sets `invalid`.
+    pub fn open(root: &Path, cache_dir: Option<&Path>, rehash: bool) -> Self {
+        let path = match cache_dir {
+            Some(dir) => {
+                let canonical = root.canonicalize().unwrap_or(root.to_owned());
+                let id = blake3::hash(canonical.as_os_str().as_encoded_bytes()).to_hex();
+                dir.join(format!("chksumcache_{}", &id[..16]))
+            }
+            None => root.join(CACHE_FILENAME),
+        };
+
+        let mut cache = Self {
+            path,
+            root: root.to_owned(),
+            hash_types: Vec::new(),
+            entries: HashMap::new(),
+            seen: HashSet::new(),
+            rehash,
+            hits: 0,
+            misses: 0,
+            corrupt: Vec::new(),
+            invalid: false,
+        };
+
+        if let Ok(content) = read_to_string(&cache.path) {
+            if !cache.parse(&content) {
+                cache.invalid = true;
+                cache.hash_types.clear();
+                cache.entries.clear();
+            }
+        }
+
+        cache
+    }
+
+    /// Returns `false` if the cache file is invalid.
+    fn parse(&mut self, content: &str) -> bool {
+        let mut lines = content.lines();
+        let Some(types) = lines.next().and_then(|h| h.strip_prefix(CACHE_HEADER)) else {
+            return false;
+        };
+        let Ok(hash_types) = types
+            .split('\t')
+            .skip(1) // leading tab
+            .map(HashType::from_str)
+            .collect::<Result<Vec<_>, _>>()
+        else {
+            return false;
+        };
+        self.hash_types = hash_types;
+
+        for line in lines.filter(|l| !l.is_empty()) {
+            let fields: Vec<&str> = line.split('\t').collect();
+            if fields.len() != 5 + self.hash_types.len() {
+                return false;
+            }
+            let (Ok(size), Ok(modified), Ok(inode)) = (fields[1].parse(), fields[2].parse(), fields[3].parse()) else {
+                return false;
+            };
+            let corrupt = match fields[4] {
+                "0" => false,
+                "1" => true,
+                _ => return false,
+            };
+            let hashes = fields[5..]
+                .iter()
+                .map(|h| (!h.is_empty()).then(|| h.to_string()))
+                .collect();
+            self.entries.insert(
+                PathBuf::from(fields[0]),
+                CacheEntry {
+                    meta: FileMeta { size, modified, inode },
+                    hashes,
+                    corrupt,
+                },
+            );
+        }
+
+        true
+    }
+
+    /// Cache file path.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn key(&self, full_path: &Path) -> PathBuf {
+        full_path.strip_prefix(&self.root).unwrap_or(full_path).to_owned()
+    }
+
+    /// Returns cached hashes for `full_path`, one per hash type in `hash_types`,
+    /// if the cache has all of them, `meta` matches the cached metadata,
+    /// and the file has not been found corrupt. Counts a hit or miss.
+    pub fn get(&mut self, full_path: &Path, meta: Option<&FileMeta>, hash_types: &[HashType]) -> Option<Vec<String>> {
+        let key = self.key(full_path);
+        let hashes = match (self.rehash, meta, self.entries.get(&key)) {
+            (false, Some(meta), Some(entry)) if &entry.meta == meta && !entry.corrupt => hash_types
+                .iter()
+                .map(|hash_type| {
+                    let index = self.hash_types.iter().position(|h| h == hash_type)?;
+                    entry.hashes[index].to_owned()
+                })
+                .collect::<Option<Vec<_>>>(),
+            _ => None,
+        };
+
+        match hashes {
+            Some(_) => self.hits += 1,
+            None => self.misses += 1,
+        }
+        self.seen.insert(key);
+
+        hashes
+    }
+
+    /// Adds or updates hashes for `full_path`. Hashes for other hash types
+    /// are kept if the file metadata is unchanged.
+    ///
+    /// If the metadata is unchanged but a cached hash differs (with `--rehash`, or for a file
+    /// found corrupt on an earlier run), the file is listed as corrupt and the cached hash is kept,
+    /// but no longer used, so that the file is re-hashed and reported again on the next run.
+    pub fn insert(&mut self, full_path: &Path, meta: FileMeta, hash_types: &[HashType], hashes: &[String]) {
+        // Tabs or line breaks in filenames would break the cache file
+        if full_path.to_string_lossy().contains(['\t', '\n', '\r']) {
+            return;
+        }
+
+        for hash_type in hash_types {
+            if !self.hash_types.contains(hash_type) {
+                self.hash_types.push(hash_type.to_owned());
+                self.entries.values_mut().for_each(|e| e.hashes.push(None));
+            }
+        }
+
+        let key = self.key(full_path);
+        let type_count = self.hash_types.len();
+        let entry = self.entries.entry(key.to_owned()).or_insert_with(|| CacheEntry {
+            meta: meta.to_owned(),
+            hashes: vec![None; type_count],
+            corrupt: false,
+        });
+        if entry.meta != meta {
+            entry.meta = meta;
+            entry.hashes = vec![None; type_count];
+            entry.corrupt = false;
+        }
+        let mut corrupt = false;
+        for (hash_type, hash) in hash_types.iter().zip(hashes.iter()) {
+            if let Some(index) = self.hash_types.iter().position(|h| h == hash_type) {
+                match &entry.hashes[index] {
+                    Some(cached) if cached != hash => corrupt = true,
+                    _ => entry.hashes[index] = Some(hash.to_owned()),
+                }
+            }
+        }
+        // cleared once the file is restored
+        entry.corrupt = corrupt;
+        if corrupt {
+            self.corrupt.push(full_path.to_owned());
+        }
+        self.seen.insert(key);
+    }
+
+    /// Hit/miss summary, e.g. `Cache: 120 hits, 3 misses (97.6% hit rate)`.
+    pub fn stats(&self) -> String {
+        let total = self.hits + self.misses;
+        let rate = match total {
+            0 => 0.0,
+            _ => 100.0 * self.hits as f64 / total as f64,
+        };
+        format!("Cache: {} hits, {} misses ({rate:.1}% hit rate)", self.hits, self.misses)
+    }
+
+    /// Writes the cache to disk. Entries not seen during this run
+    /// are dropped if the file no longer exists.
+    /// The cache file is replaced atomically, without confirmation.
+    pub fn save(&mut self) -> std::io::Result<()> {
+        let (root, seen) = (&self.root, &self.seen);
+        self.entries.retain(|key, _| seen.contains(key) || root.join(key).exists());
+
+        let mut keys: Vec<&PathBuf> = self.entries.keys().collect();
+        keys.sort();
+
+        let mut content = CACHE_HEADER.to_owned();
+        for hash_type in self.hash_types.iter() {
+            content.push_str(&format!("\t{hash_type}"));
+        }
+        content.push('\n');
+        for key in keys {
+            let entry = &self.entries[key];
+            content.push_str(&format!(
+                "{}\t{}\t{}\t{}\t{}\t{}\n",
+                key.display(),
+                entry.meta.size,
+                entry.meta.modified,
+                entry.meta.inode,
+                entry.corrupt as u8,
+                entry
+                    .hashes
+                    .iter()
+                    .map(|h| h.as_deref().unwrap_or(""))
+                    .collect::<Vec<_>>()
+                    .join("\t")
+            ));
+        }
+
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let tmp_path = self.path.with_extension("tmp");
+        std::fs::write(&tmp_path, content)?;
+        std::fs::rename(&tmp_path, &self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn meta(size: u64) -> FileMeta {
+        FileMeta { size, modified: 1_700_000_000_123_456_789, inode: 42 }
+    }
+
+    #[test]
+    fn save_and_parse_round_trip() {
+        let root = std::env::temp_dir().join(format!("chksum_cache_test_{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        let hash_types = [HashType::Blake3, HashType::Sha256.chunked(1000)];
+
+        let mut cache = HashCache::open(&root, None, false);
+        assert!(!cache.invalid);
+        cache.insert(&root.join("a.txt"), meta(1), &hash_types, &["aa".to_owned(), "bb".to_owned()]);
+        cache.insert(&root.join("dir/b.txt"), meta(2), &hash_types[..1], &["cc".to_owned()]);
+        cache.save().unwrap();
+
+        let mut cache = HashCache::open(&root, None, false);
+        assert!(!cache.invalid);
+        assert_eq!(cache.hash_types, hash_types);
+        assert_eq!(cache.get(&root.join("a.txt"), Some(&meta(1)), &hash_types), Some(vec!["aa".to_owned(), "bb".to_owned()]));
+        assert_eq!(cache.get(&root.join("a.txt"), Some(&meta(1)), &hash_types[1..]), Some(vec!["bb".to_owned()]));
+        assert_eq!(cache.get(&root.join("dir/b.txt"), Some(&meta(2)), &hash_types[..1]), Some(vec!["cc".to_owned()]));
+        // hash type not cached, metadata changed
+        assert_eq!(cache.get(&root.join("dir/b.txt"), Some(&meta(2)), &hash_types), None);
+        assert_eq!(cache.get(&root.join("a.txt"), Some(&meta(3)), &hash_types), None);
+        assert_eq!((cache.hits, cache.misses), (3, 2));
+
+        std::fs::write(cache.path(), "not a cache").unwrap();
+        let cache = HashCache::open(&root, None, false);
+        assert!(cache.invalid);
+        assert!(cache.entries.is_empty());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn corrupt_files_are_reported_until_restored() {
+        let root = std::env::temp_dir().join(format!("chksum_cache_corrupt_test_{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        let hash_types = [HashType::Blake3];
+        let path = root.join("a.txt");
+
+        let mut cache = HashCache::open(&root, None, false);
+        cache.insert(&path, meta(1), &hash_types, &["aa".to_owned()]);
+        cache.save().unwrap();
+
+        // hash changed with unchanged metadata
+        let mut cache = HashCache::open(&root, None, true);
+        assert_eq!(cache.get(&path, Some(&meta(1)), &hash_types), None);
+        cache.insert(&path, meta(1), &hash_types, &["bb".to_owned()]);
+        assert_eq!(cache.corrupt, [path.to_owned()]);
+        cache.save().unwrap();
+
+        // re-hashed and reported again without --rehash
+        let mut cache = HashCache::open(&root, None, false);
+        assert_eq!(cache.get(&path, Some(&meta(1)), &hash_types), None);
+        cache.insert(&path, meta(1), &hash_types, &["bb".to_owned()]);
+        assert_eq!(cache.corrupt, [path.to_owned()]);
+        cache.save().unwrap();
+
+        // restored
+        let mut cache = HashCache::open(&root, None, false);
+        cache.insert(&path, meta(1), &hash_types, &["aa".to_owned()]);
+        assert!(cache.corrupt.is_empty());
+        cache.save().unwrap();
+        let mut cache = HashCache::open(&root, None, false);
+        assert_eq!(cache.get(&path, Some(&meta(1)), &hash_types), Some(vec!["aa".to_owned()]));
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
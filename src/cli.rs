@@ -0,0 +1,368 @@
+use std::path::PathBuf;
+
+use clap::{Arg, ArgAction, ArgGroup, Command};
+
+use chksum::datetime::parse_datetime;
+use chksum::hash::{HashType, IoStrategy};
+use chksum::stats::Units;
+
+use crate::tasks;
+
+const VERSION: &str = "0.5.0";
+
+/// Returns the command line interface.
+pub fn command() -> Command {
+    Command::new("chksum")
+        .version(VERSION)
+        .author("Jens Larsson <jenslar@fastmail.com>")
+        .term_width(80)
+        .about("Calculate BLAKE3 (default), SHA-256, SHA-512, SHA3-256, BLAKE2b, SHA-1, or MD5 (see MD5/SHA-1 security note) checksum for all files in source directory recursively.
+Optionally compare and match with all files in target directory recursively. Other uses are finding duplicate files,
+or list an overview of total size of file types (file extension) in source directory.
+
+NOTE: MD5 and SHA-1 have security issues and are only included for verifying existing hashes (they allow for collisions, e.g. two different files may return the same hash). Use BLAKE3 or SHA256 instead. See: https://www.kb.cert.org/vuls/id/836068, https://dl.acm.org/doi/10.1109/CIS.2009.214, and the conclusion https://datatracker.ietf.org/doc/html/rfc6151. For SHA-1, see https://shattered.io.
+
+NOTE: XXH3 and CRC32C are fast, non-cryptographic hashes intended for quick integrity sweeps (detecting bit rot). They offer no protection against deliberate tampering.
+
+NOTE: SHA256 checksums do not match BLAKE3 checksums. BLAKE3 is the faster of the two. Install the 'b3sum'
+utility if there is a need to verify BLAKE3 checksums for individual files (https://github.com/BLAKE3-team/BLAKE3).")
+        .after_help("Exit codes (the first that applies is used):
+  0  Success. All files match, verify OK, or no duplicates found.
+  1  Runtime error, e.g. source directory or manifest could not be read.
+  2  Invalid arguments.
+  3  Corrupt files found (hash changed, size and modification time did not).
+  4  Files could not be read with '--continue-on-error'.
+  5  Differences found: files missing, changed, new, or moved (see '--fail-on'), or failed/missing when verifying.
+  6  Duplicate files found.")
+        .arg(Arg::new("source-dir")
+            .help("Calculate checksums for all files in this path recursively.")
+            .short('s')
+            .long("source-dir")
+            .value_parser(clap::value_parser!(PathBuf))
+            .required_unless_present_any(["verify", "source-manifest"]))
+        .arg(Arg::new("target-dir")
+            .help("If passed, file checksums will be compared to those for source-dir.")
+            .short('t')
+            .long("target-dir")
+            .value_parser(clap::value_parser!(PathBuf)))
+        .arg(Arg::new("source-manifest")
+            .help("Use a checksum log (e.g. 'checksums.csv') or GNU coreutils manifest (e.g. 'SHA256SUMS') instead of hashing source-dir. Requires target-dir or target-manifest.")
+            .long("source-manifest")
+            .conflicts_with_all(["source-dir", "verify", "duplicates", "stats"])
+            .requires("target")
+            .value_parser(clap::value_parser!(PathBuf)))
+        .arg(Arg::new("target-manifest")
+            .help("Use a checksum log (e.g. 'checksums.csv') or GNU coreutils manifest (e.g. 'SHA256SUMS') instead of hashing target-dir.")
+            .long("target-manifest")
+            .value_parser(clap::value_parser!(PathBuf)))
+        .group(ArgGroup::new("target")
+            .args(["target-dir", "target-manifest"]))
+        .arg(Arg::new("verify")
+            .help("Verify files listed in a checksum log written via 'log' (e.g. 'checksums.csv') or in a GNU coreutils manifest (e.g. 'SHA256SUMS'). Files are re-hashed and reported as OK, FAILED, or MISSING. Paths relative to the hashed directory are resolved against 'source-dir' if set, e.g. to verify a copy.")
+            .long("verify")
+            .conflicts_with_all(["target", "duplicates", "stats"])
+            .value_parser(clap::value_parser!(PathBuf)))
+        .arg(Arg::new("exclude-dir")
+            .help("Exclude any directory with this name (not path).")
+            .long("exclude-dir")
+            .alias("ed")
+            .num_args(1..)
+            .value_parser(clap::value_parser!(String)))
+        .arg(Arg::new("exclude-path")
+            .help("Exclude explicit paths.")
+            .long("exclude-path")
+            .alias("ep")
+            .num_args(1..)
+            .value_parser(clap::value_parser!(String)))
+        .arg(Arg::new("include-ext")
+            .help("File extensions to consider. Ignores all other files.")
+            .long("include-ext")
+            .alias("ie")
+            .num_args(1..)
+            .value_parser(clap::value_parser!(String)))
+        .arg(Arg::new("exclude-ext")
+            .help("File extensions to exclude.")
+            .long("exclude-ext")
+            .alias("ee")
+            .num_args(1..)
+            .value_parser(clap::value_parser!(String)))
+        .arg(Arg::new("include")
+            .help("Glob patterns for files to consider, matched against the path relative to source-dir/target-dir, e.g. '**/*.jpg'. Ignores all other files. As for '.gitignore', patterns without '/' match names at any depth, and a leading '/' anchors the pattern to the root.")
+            .long("include")
+            .num_args(1..)
+            .value_parser(tasks::parse_glob))
+        .arg(Arg::new("exclude")
+            .help("Glob patterns for files and directories to exclude, e.g. '**/cache/**' '*.tmp' 'Thumbs.db'. A trailing '/' matches directories, e.g. 'cache/' excludes any directory named 'cache'. Excluded directories are not traversed. Quote patterns to prevent shell expansion.")
+            .long("exclude")
+            .num_args(1..)
+            .value_parser(tasks::parse_glob))
+        .arg(Arg::new("use-ignore-files")
+            .help("Skip files and directories matched by '.gitignore', '.ignore', or '.chksumignore' files (gitignore syntax) at any level of source-dir/target-dir. Rules closer to a file take precedence, and '.chksumignore' takes precedence over '.ignore', which takes precedence over '.gitignore'.")
+            .long("use-ignore-files")
+            .action(ArgAction::SetTrue))
+        .arg(Arg::new("min-size")
+            .help("Only files of at least this size, in bytes or with a unit suffix: KB, MB, GB, TB (e.g. '500KB', '1.5GB'). Applies to source-dir and target-dir alike.")
+            .long("min-size")
+            .value_parser(Units::parse_bytes))
+        .arg(Arg::new("max-size")
+            .help("Only files of at most this size, in bytes or with a unit suffix: KB, MB, GB, TB. Applies to source-dir and target-dir alike.")
+            .long("max-size")
+            .value_parser(Units::parse_bytes))
+        .arg(Arg::new("newer-than")
+            .help("Only files modified after a UTC date/datetime (e.g. '2026-01-31', '2026-01-31 12:00:00') or within a duration before now (e.g. '30m', '12h', '7d', '2w'). Applies to source-dir and target-dir alike.")
+            .long("newer-than")
+            .value_parser(parse_datetime))
+        .arg(Arg::new("older-than")
+            .help("Only files modified before a UTC date/datetime or longer than a duration ago (see 'newer-than'). Applies to source-dir and target-dir alike.")
+            .long("older-than")
+            .value_parser(parse_datetime))
+        .arg(Arg::new("include-regex")
+            .help("Regular expressions for files to consider, matched against the path relative to source-dir/target-dir with '/' as separator. Ignores all other files.")
+            .long("include-regex")
+            .num_args(1..)
+            .value_parser(tasks::parse_regex))
+        .arg(Arg::new("exclude-regex")
+            .help("Regular expressions for files and directories to exclude, matched against the relative path. Directories are matched with a trailing '/' (e.g. '(^|/)cache/$') and not traversed if excluded.")
+            .long("exclude-regex")
+            .num_args(1..)
+            .value_parser(tasks::parse_regex))
+        .arg(Arg::new("log")
+            .help("Log hashes and paths as CSV files (see 'delimiter', 'quote-all', and 'bom').")
+            .short('l')
+            .long("log")
+            .action(ArgAction::SetTrue))
+        .arg(Arg::new("sums")
+            .help("Write a GNU coreutils manifest (e.g. 'SHA256SUMS') with paths relative to source-dir to the log directory. Can be checked with e.g. 'sha256sum --check' from within source-dir.")
+            .long("sums")
+            .conflicts_with_all(["duplicates", "stats"])
+            .action(ArgAction::SetTrue))
+        .arg(Arg::new("delimiter")
+            .help("Field delimiter for CSV logs: 'comma' (default), 'tab', 'semicolon', or any single ASCII character.")
+            .long("delimiter")
+            .default_value("comma")
+            .value_parser(tasks::parse_delimiter))
+        .arg(Arg::new("quote-all")
+            .help("Quote all fields in CSV logs, not only those containing the delimiter, quotes, or line breaks.")
+            .long("quote-all")
+            .action(ArgAction::SetTrue))
+        .arg(Arg::new("bom")
+            .help("Start CSV logs with a UTF-8 byte order mark, so that e.g. Excel detects the encoding.")
+            .long("bom")
+            .action(ArgAction::SetTrue))
+        .arg(Arg::new("report")
+            .help("Write a self-contained HTML report with totals, sortable file tables, and file type statistics to the specified path. Requires target-dir, target-manifest, or duplicates.")
+            .long("report")
+            .value_parser(clap::value_parser!(PathBuf))
+            .requires("report-run")
+            .conflicts_with_all(["verify", "stats"]))
+        .group(ArgGroup::new("report-run")
+            .args(["target-dir", "target-manifest", "duplicates"])
+            .multiple(true))
+        .arg(Arg::new("log-dir")
+            .help("Custom log directory.")
+            .long("log-dir")
+            .alias("ld")
+            .value_parser(clap::value_parser!(PathBuf)))
+        .arg(Arg::new("run-dir")
+            .help("Write logs to a new sub-directory of the log directory named after the current UTC time, e.g. 'chksum_logs/2026-10-16T10-00-00/', so that logs from earlier runs are never overwritten.")
+            .long("run-dir")
+            .action(ArgAction::SetTrue))
+        .arg(Arg::new("overwrite")
+            .help("Overwrite existing logs and reports without asking.")
+            .long("overwrite")
+            .conflicts_with("no-clobber")
+            .action(ArgAction::SetTrue))
+        .arg(Arg::new("no-clobber")
+            .help("Keep existing logs and reports without asking. New results for existing files are not written. Default if stdin is not a terminal (e.g. cron), otherwise the user is asked.")
+            .long("no-clobber")
+            .action(ArgAction::SetTrue))
+        .arg(Arg::new("duplicates")
+            .help("Find duplicate files.")
+            .long("duplicates")
+            .conflicts_with("target")
+            .action(ArgAction::SetTrue))
+        .arg(Arg::new("case-sensitive")
+            .help("Case sensitive file extensions. Count e.g. 'mp4' and 'MP4' separately. Only valid if 'stats' is passed.")
+            .long("case")
+            .requires("stats")
+            .action(ArgAction::SetTrue))
+        .arg(Arg::new("verbose")
+            .help("Print each encountered file.")
+            .short('v')
+            .long("verbose")
+            .action(ArgAction::SetTrue))
+        .arg(Arg::new("include-hidden")
+            .help("Include hidden files (filename starts with '.'). Currently only works for unix/linux.")
+            .long("hidden")
+            .action(ArgAction::SetTrue))
+        .arg(Arg::new("follow-symlinks")
+            .help("Follow symlinks. Symlinks will otherwise be ignored.")
+            .long("symlinks")
+            .action(ArgAction::SetTrue))
+        .arg(Arg::new("partial-hash-algo")
+            .help("Hashing algorithm for duplicate quick check, e.g. 'xxh3'. Defaults to the (first) selected hashing algorithm.")
+            .long("paralgo")
+            .requires("duplicates")
+            .value_parser(clap::value_parser!(HashType)))
+        .arg(Arg::new("partial-hash-size")
+            .help("Partial hash size for duplicate quick check.")
+            .long("parsize")
+            .default_value("1000")
+            .requires("duplicates")
+            .value_parser(clap::value_parser!(usize)))
+        .arg(Arg::new("cache")
+            .help("Cache hashes in '.chksumcache' in the root of each hashed directory. Files with unchanged size, modification time, and inode are not re-hashed on the next run.")
+            .long("cache")
+            .conflicts_with("stats")
+            .action(ArgAction::SetTrue))
+        .arg(Arg::new("cache-dir")
+            .help("Store hash caches in this directory instead, one file per hashed directory. Implies 'cache'.")
+            .long("cache-dir")
+            .conflicts_with("stats")
+            .value_parser(clap::value_parser!(PathBuf)))
+        .group(ArgGroup::new("caching")
+            .args(["cache", "cache-dir"])
+            .multiple(true))
+        .arg(Arg::new("rehash")
+            .help("Ignore cached hashes and re-hash all files. The cache is still updated.")
+            .long("rehash")
+            .requires("caching")
+            .action(ArgAction::SetTrue))
+        .arg(Arg::new("cache-stats")
+            .help("Print number of cache hits and misses.")
+            .long("cache-stats")
+            .requires("caching")
+            .action(ArgAction::SetTrue))
+        .arg(Arg::new("sha256")
+            .help("Use the SHA-256 hashing algorithm instead of the default, faster Blake3.")
+            .long("sha256")
+            .action(ArgAction::SetTrue))
+        .arg(Arg::new("md5")
+            .help("Use MD5 hashing algorithm instead of the default Blake3.")
+            .long("md5")
+            .action(ArgAction::SetTrue))
+        .arg(Arg::new("sha512")
+            .help("Use the SHA-512 hashing algorithm instead of the default Blake3.")
+            .long("sha512")
+            .action(ArgAction::SetTrue))
+        .arg(Arg::new("sha1")
+            .help("Use SHA-1 hashing algorithm instead of the default Blake3.")
+            .long("sha1")
+            .action(ArgAction::SetTrue))
+        .arg(Arg::new("sha3-256")
+            .help("Use the SHA3-256 hashing algorithm instead of the default Blake3.")
+            .long("sha3-256")
+            .alias("sha3")
+            .action(ArgAction::SetTrue))
+        .arg(Arg::new("blake2b")
+            .help("Use the BLAKE2b (512-bit) hashing algorithm instead of the default Blake3.")
+            .long("blake2b")
+            .action(ArgAction::SetTrue))
+        .arg(Arg::new("xxh3")
+            .help("Use the fast, non-cryptographic 64-bit XXH3 hashing algorithm instead of the default Blake3.")
+            .long("xxh3")
+            .action(ArgAction::SetTrue))
+        .arg(Arg::new("xxh128")
+            .help("Use the fast, non-cryptographic 128-bit XXH3 hashing algorithm instead of the default Blake3.")
+            .long("xxh128")
+            .action(ArgAction::SetTrue))
+        .arg(Arg::new("crc32c")
+            .help("Use the fast, non-cryptographic CRC-32C checksum instead of the default Blake3.")
+            .long("crc32c")
+            .action(ArgAction::SetTrue))
+        .arg(Arg::new("algo")
+            .help("Use one or more hashing algorithms, comma separated (e.g. 'md5,sha256,blake3'). Each file is only read once. Logs will contain one column per algorithm. Possible values: blake3, sha256, sha512, sha3-256, blake2b, sha1, md5, xxh3, xxh128, crc32c.")
+            .long("algo")
+            .value_delimiter(',')
+            .num_args(1..)
+            .value_parser(clap::value_parser!(HashType)))
+        .group(ArgGroup::new("algorithm")
+            .args(["sha256", "md5", "sha512", "sha1", "sha3-256", "blake2b", "xxh3", "xxh128", "crc32c", "algo"]))
+        .arg(Arg::new("chunk-size")
+            .help("Chunked hashing: split files into chunks of this size, in bytes or with a unit suffix: KB, MB, GB, TB (e.g. '16MB'), and hash the chunk hashes. Chunks of large files (see 'parallel-hash-above') are hashed in parallel for any algorithm, buffering up to one chunk per core (at most 256MB). Hashes are labelled e.g. 'SHA256-CHUNKED-16000000' in logs and differ from those of regular tools (e.g. sha256sum). Manifests record the chunk size, which takes precedence when comparing.")
+            .long("chunk-size")
+            .conflicts_with_all(["verify", "sums"])
+            .value_parser(tasks::parse_size))
+        .arg(Arg::new("stats")
+            .help("Returns an overview of source-dir.")
+            .long("stats")
+            .action(ArgAction::SetTrue))
+        .arg(Arg::new("benchmark")
+            .help("Hash all files in source-dir once per I/O strategy (see 'io') and print the throughput in MB/s for each. On Linux, files are dropped from the page cache before each pass, so that all are read from disk.")
+            .long("benchmark")
+            .requires("source-dir")
+            .conflicts_with_all(["target", "verify", "duplicates", "stats", "io", "log", "sums", "report", "caching"])
+            .action(ArgAction::SetTrue))
+        .arg(Arg::new("threshold")
+            .help("Threshold in percent. Filetypes below threshold volume will not be shown for 'stats'.")
+            .long("threshold")
+            .default_value("1.0")
+            .value_parser(clap::value_parser!(f64)))
+        .arg(Arg::new("stats-sort-count")
+            .help("Sort file stats on file extension count.")
+            .long("sort-count")
+            .alias("sc")
+            .requires("stats")
+            .conflicts_with_all(["stats-sort-size", "stats-sort-alpha"])
+            .action(ArgAction::SetTrue))
+        .arg(Arg::new("stats-sort-size")
+            .help("Sort file stats on total file size.")
+            .long("sort-size")
+            .alias("sz")
+            .requires("stats")
+            .conflicts_with_all(["stats-sort-count", "stats-sort-alpha"])
+            .action(ArgAction::SetTrue))
+        .arg(Arg::new("stats-sort-alpha")
+            .help("Sort file stats on file extension alphabetically.")
+            .long("sort-alpha")
+            .alias("sa")
+            .requires("stats")
+            .conflicts_with_all(["stats-sort-count", "stats-sort-size"])
+            .action(ArgAction::SetTrue))
+        .arg(Arg::new("parallel-walk")
+            .help("Walk directories in several threads. Faster for very large trees, e.g. millions of small files or network file systems, where listing directories is slower than hashing. Files are found in no particular order.")
+            .long("parallel-walk")
+            .alias("pw")
+            .action(ArgAction::SetTrue))
+        .arg(Arg::new("parallel-hash-above")
+            .help("Hash files of at least this size in several threads, in bytes or with a unit suffix: KB, MB, GB, TB. Large files are read in buffers of 'buffer-size' (or memory mapped with '--io mmap'), BLAKE3 and chunked hashes use all cores for a single file, other hash types one thread each. Hashes are the same as for smaller files.")
+            .long("parallel-hash-above")
+            .default_value("128MB")
+            .value_parser(Units::parse_bytes))
+        .arg(Arg::new("io")
+            .help("How files are read: 'auto' reads via a small buffer, or as for 'buffered' above 'parallel-hash-above', 'buffered' reads into a page aligned buffer (see 'buffer-size'), 'mmap' memory maps all files. Hashes are the same for all. With 'mmap', a file truncated while hashed or a read error (e.g. failing or network drive) aborts chksum instead of being reported, also with 'continue-on-error'. See 'benchmark' for the fastest on a given drive.")
+            .long("io")
+            .default_value("auto")
+            .value_parser(clap::value_parser!(IoStrategy)))
+        .arg(Arg::new("buffer-size")
+            .help("Read buffer size for '--io buffered', in bytes or with a unit suffix: KB, MB, GB (e.g. '16MB'). Rounded up to a multiple of 4096 bytes. Defaults to 1048576 bytes.")
+            .long("buffer-size")
+            .value_parser(tasks::parse_size))
+        .arg(Arg::new("fadvise")
+            .help("Hint that files are read sequentially (posix_fadvise), so that the kernel reads ahead more aggressively, e.g. for spinning disks. Linux only, ignored elsewhere.")
+            .long("fadvise")
+            .action(ArgAction::SetTrue))
+        .arg(Arg::new("ignore-path-errors")
+            .help("Ignore errors when compiling paths.")
+            .long("ignore-path-errors")
+            .alias("ipe")
+            .action(ArgAction::SetTrue))
+        .arg(Arg::new("format")
+            .help("Output format. 'json' prints comparison results as a single JSON document with totals, hash algorithms, and all matched, missing, moved, changed, corrupt, and ignored files, and errors, instead of the text summary. Requires target-dir or target-manifest. 'ndjson' prints one JSON event per file as soon as it is hashed, and a final summary event. Not supported for verify, duplicates, and stats.")
+            .long("format")
+            .default_value("text")
+            .value_parser(["text", "json", "ndjson"])
+            .requires_if("json", "target"))
+        .arg(Arg::new("fail-on")
+            .help("Difference categories that result in exit code 5 when comparing or verifying, comma separated: missing, changed, new, moved, or none. Verify counts FAILED as changed and MISSING as missing. Corrupt and unreadable files always have their own exit codes.")
+            .long("fail-on")
+            .value_delimiter(',')
+            .num_args(1..)
+            .default_values(["missing", "changed", "new", "moved"])
+            .value_parser(["missing", "changed", "new", "moved", "none"]))
+        .arg(Arg::new("continue-on-error")
+            .help("Skip files and directories that can not be read (e.g. permission denied, deleted during run) instead of aborting. Skipped files are listed at the end and logged to 'errors.csv'.")
+            .long("continue-on-error")
+            .alias("coe")
+            .action(ArgAction::SetTrue))
+}
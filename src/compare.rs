@@ -0,0 +1,611 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use serde_json::{json, Value};
+
+use crate::{
+    cache::HashCache,
+    datetime::now_to_string,
+    errors::ChksumError,
+    files::{checksum_header, checksum_row, file_info, filename_to_string, is_corrupt, FileInfo},
+    hash::{
+        hash_dir, hash_headers, hash_types_to_string, hashes_to_json, is_legacy_match, match_moved,
+        HashContext, HashOptions, HashType, Hashes, OnHashed,
+    },
+    manifest::Manifest,
+    walk::WalkOptions,
+};
+
+/// Directory to hash, or manifest to read hashes from, for one side of a comparison.
+#[derive(Debug, Clone, Copy)]
+pub enum TreeSource<'a> {
+    Dir(&'a Path),
+    Manifest(&'a Manifest),
+}
+
+impl<'a> TreeSource<'a> {
+    fn manifest(&self) -> Option<&'a Manifest> {
+        match self {
+            TreeSource::Dir(_) => None,
+            TreeSource::Manifest(manifest) => Some(manifest),
+        }
+    }
+}
+
+/// Options for `compare_trees`.
+#[derive(Debug, Clone, Default)]
+pub struct CompareOptions {
+    /// Hash types for directories, ignored if either side is a manifest.
+    /// See `comparison_hash_types`.
+    pub hash_types: Vec<HashType>,
+    pub walk_options: WalkOptions,
+    pub hash_options: HashOptions,
+    /// Skip files that can not be hashed and list them in `HashedTree::errors`,
+    /// instead of returning the first error.
+    pub continue_on_error: bool,
+}
+
+/// Optional state for hashing one side in `compare_trees`, see `HashContext`.
+/// Not used for manifests.
+#[derive(Default)]
+pub struct TreeContext<'a> {
+    pub cache: Option<&'a mut HashCache>,
+    pub on_hashed: Option<OnHashed<'a>>,
+}
+
+/// Result of `compare_trees`.
+#[derive(Debug)]
+pub struct Comparison {
+    /// Hash types for both sides.
+    pub hash_types: Vec<HashType>,
+    pub source: HashedTree,
+    /// `None` if no target was set.
+    pub target: Option<HashedTree>,
+    /// `None` if no target was set.
+    pub report: Option<ComparisonReport>,
+}
+
+/// Returns hash types for comparing `source` with `target`. Manifests can not be re-hashed,
+/// so if either side is a manifest its hash types are used, otherwise `hash_types`.
+/// Returns an error if both sides are manifests with different hash types.
+pub fn comparison_hash_types(
+    source: TreeSource,
+    target: Option<TreeSource>,
+    hash_types: &[HashType],
+) -> Result<Vec<HashType>, ChksumError> {
+    match (source.manifest(), target.and_then(|t| t.manifest())) {
+        (Some(s), Some(t)) if s.hash_types != t.hash_types => Err(ChksumError::HashTypeMismatch((
+            hash_types_to_string(&s.hash_types),
+            hash_types_to_string(&t.hash_types),
+        ))),
+        (Some(m), _) | (None, Some(m)) => Ok(m.hash_types.to_owned()),
+        (None, None) => Ok(hash_types.to_vec()),
+    }
+}
+
+/// Hashes or reads `source`, and `target` if set, and compares them.
+/// Directories are hashed while walked with the hash types returned by
+/// `comparison_hash_types`, see `hash_dir`.
+pub fn compare_trees(
+    source: TreeSource,
+    target: Option<TreeSource>,
+    options: &CompareOptions,
+    source_context: TreeContext,
+    target_context: TreeContext,
+) -> Result<Comparison, ChksumError> {
+    let hash_types = comparison_hash_types(source, target, &options.hash_types)?;
+
+    let hashed_tree = |tree: TreeSource, context: TreeContext| -> Result<HashedTree, ChksumError> {
+        match tree {
+            TreeSource::Manifest(manifest) => Ok(HashedTree::from_manifest(manifest)),
+            TreeSource::Dir(dir) => {
+                let mut errors: Vec<ChksumError> = Vec::new();
+                let hashes = hash_dir(
+                    dir,
+                    &options.walk_options,
+                    &hash_types,
+                    &options.hash_options,
+                    HashContext {
+                        cache: context.cache,
+                        errors: options.continue_on_error.then_some(&mut errors),
+                        on_hashed: context.on_hashed,
+                    },
+                )?;
+                Ok(HashedTree::from_dir(dir, hashes, errors))
+            }
+        }
+    };
+
+    let source = hashed_tree(source, source_context)?;
+    let target = target
+        .map(|target| hashed_tree(target, target_context))
+        .transpose()?;
+    let report = target.as_ref().map(|target| compare(&source, target));
+
+    Ok(Comparison { hash_types, source, target, report })
+}
+
+/// Hashes for one side of a comparison, either a hashed directory or a manifest.
+#[derive(Debug, Default)]
+pub struct HashedTree {
+    /// Hashed directory, `None` for manifests.
+    pub root: Option<PathBuf>,
+    /// Hashes keyed on relative path.
+    pub hashes: Hashes,
+    /// Size and modification time as logged in a manifest, keyed on relative path.
+    /// Read from disk if `None`.
+    pub info: Option<HashMap<PathBuf, FileInfo>>,
+    /// Files that could not be hashed.
+    pub errors: Vec<ChksumError>,
+}
+
+impl HashedTree {
+    /// Hashes for directory `root`, as returned by `hash_files`
+    /// with `root` as prefix to strip.
+    pub fn from_dir(root: &Path, hashes: Hashes, errors: Vec<ChksumError>) -> Self {
+        Self {
+            root: Some(root.to_owned()),
+            hashes,
+            info: None,
+            errors,
+        }
+    }
+
+    pub fn from_manifest(manifest: &Manifest) -> Self {
+        Self {
+            root: None,
+            hashes: manifest.to_hashes(),
+            info: Some(manifest.file_info()),
+            errors: Vec::new(),
+        }
+    }
+
+    /// Returns full path (or path as logged for manifests) for `relative_path`.
+    pub fn full_path(&self, relative_path: &Path) -> Option<&Path> {
+        self.hashes.get(relative_path).map(|(path, _)| path.as_path())
+    }
+
+    /// Returns hashes for `relative_path`, one per hash type.
+    pub fn hashes(&self, relative_path: &Path) -> Option<&[String]> {
+        self.hashes.get(relative_path).map(|(_, hashes)| hashes.as_slice())
+    }
+
+    /// Returns size and modification time for `relative_path`,
+    /// from the manifest if logged, otherwise from disk.
+    pub fn file_info(&self, relative_path: &Path) -> FileInfo {
+        match &self.info {
+            Some(info) => info.get(relative_path).cloned().unwrap_or_default(),
+            None => self
+                .full_path(relative_path)
+                .map(file_info)
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Returns a checksum log with header, one row per file, see `checksum_row`.
+    /// `path_column` names the full path column, e.g. `SOURCEPATH`.
+    pub fn to_log(&self, hash_types: &[HashType], path_column: &str) -> Vec<Vec<String>> {
+        std::iter::once(checksum_header(path_column, hash_types))
+            .chain(self.hashes.iter().map(|(relative_path, (path, hashes))| {
+                // size and modification time are read from manifests if logged
+                checksum_row(path, relative_path, self.file_info(relative_path), hashes)
+            }))
+            .collect()
+    }
+
+    /// Relative paths for files that could not be hashed.
+    fn errored(&self) -> HashSet<PathBuf> {
+        self.errors
+            .iter()
+            .filter_map(|err| {
+                let path = err.path()?;
+                let relative_path = self.root
+                    .as_ref()
+                    .and_then(|root| path.strip_prefix(root).ok())
+                    .unwrap_or(path);
+                Some(relative_path.to_owned())
+            })
+            .collect()
+    }
+}
+
+/// Result of comparing source with target.
+/// All paths are relative and sorted.
+#[derive(Debug, Clone, Default)]
+pub struct ComparisonReport {
+    /// Matching paths and hashes.
+    pub matched: Vec<PathBuf>,
+    /// Matching paths, hashes only match as logged by chksum up to 2024-11-06
+    /// on one side, see `legacy_digest`.
+    pub legacy: Vec<PathBuf>,
+    /// Only in source, assume missing/not synced.
+    pub missing: Vec<PathBuf>,
+    /// Only in target, assume new/updated.
+    pub ignored: Vec<PathBuf>,
+    /// Path in both source and target, but hash not matching, assume changed in target.
+    pub changed: Vec<PathBuf>,
+    /// Path in both source and target, hash not matching, but size and modification time do,
+    /// assume silent corruption.
+    pub corrupt: Vec<PathBuf>,
+    /// File missing in target matching a file not in source by hash,
+    /// assume moved/renamed. `(<SOURCE_PATH>, <TARGET_PATH>)`.
+    /// Empty files are never matched.
+    pub moved: Vec<(PathBuf, PathBuf)>,
+}
+
+/// Compares source and target on relative path and hashes.
+///
+/// Files that could not be hashed on one side are not reported
+/// as missing on the other.
+pub fn compare(source: &HashedTree, target: &HashedTree) -> ComparisonReport {
+    let mut matched: Vec<PathBuf> = Vec::new();
+    let mut legacy: Vec<PathBuf> = Vec::new();
+    let mut changed: Vec<PathBuf> = Vec::new();
+    let mut corrupt: Vec<PathBuf> = Vec::new();
+    let mut missing: HashSet<PathBuf> = HashSet::new();
+    let mut ignored: HashSet<PathBuf> = HashSet::new();
+
+    for (path, (_, source_hash)) in source.hashes.iter() {
+        match target.hashes(path) {
+            Some(target_hash) if source_hash == target_hash => matched.push(path.to_owned()),
+            Some(target_hash) if is_legacy_match(source_hash, target_hash) || is_legacy_match(target_hash, source_hash) => {
+                legacy.push(path.to_owned())
+            }
+            // hash changed, but size and modification time did not, assume silent corruption
+            Some(_) if is_corrupt(&source.file_info(path), &target.file_info(path)) => corrupt.push(path.to_owned()),
+            Some(_) => changed.push(path.to_owned()),
+            None => {
+                missing.insert(path.to_owned());
+            }
+        }
+    }
+
+    for path in target.hashes.keys() {
+        if !source.hashes.contains_key(path) {
+            ignored.insert(path.to_owned());
+        }
+    }
+
+    // file missing in target and file not in source with identical hash,
+    // assume file was moved/renamed. Empty files all share a hash, so are left out.
+    let non_empty = |paths: &HashSet<PathBuf>, tree: &HashedTree| -> HashSet<PathBuf> {
+        paths.iter()
+            .filter(|path| tree.file_info(path).0 != Some(0))
+            .cloned()
+            .collect()
+    };
+    let moved = match_moved(&non_empty(&missing, source), &non_empty(&ignored, target), &source.hashes, &target.hashes);
+    for (source_path, target_path) in moved.iter() {
+        missing.remove(source_path);
+        ignored.remove(target_path);
+    }
+
+    // unreadable files are listed as errors, not as missing on the other side
+    let source_errored = source.errored();
+    let target_errored = target.errored();
+    missing.retain(|path| !target_errored.contains(path));
+    ignored.retain(|path| !source_errored.contains(path));
+
+    let sorted = |paths: HashSet<PathBuf>| {
+        let mut paths: Vec<PathBuf> = paths.into_iter().collect();
+        paths.sort();
+        paths
+    };
+    matched.sort();
+    legacy.sort();
+    changed.sort();
+    corrupt.sort();
+
+    ComparisonReport {
+        matched,
+        legacy,
+        missing: sorted(missing),
+        ignored: sorted(ignored),
+        changed,
+        corrupt,
+        moved,
+    }
+}
+
+/// CSV log for one category of a `ComparisonReport`, see `ComparisonReport::to_logs`.
+#[derive(Debug, Clone)]
+pub struct ComparisonLog {
+    /// Category, e.g. `missing`.
+    pub category: &'static str,
+    /// Log file name, e.g. `missing_in_target.csv`.
+    pub file_name: &'static str,
+    /// Rows, including header.
+    pub rows: Vec<Vec<String>>,
+}
+
+impl ComparisonLog {
+    /// Returns `true` if the log has no rows besides the header.
+    pub fn is_empty(&self) -> bool {
+        self.rows.len() <= 1
+    }
+}
+
+impl ComparisonReport {
+    /// Returns one CSV log per category: missing, changed, corrupt, ignored (missing in source),
+    /// moved, and matched. Matched includes files matching a legacy digest.
+    pub fn to_logs(&self, source: &HashedTree, target: &HashedTree, hash_types: &[HashType]) -> Vec<ComparisonLog> {
+        let columns = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+        let hash_columns = hash_headers(hash_types, "");
+        let filename = |path: &Path| filename_to_string(path).unwrap_or("FILENAME ERROR".to_owned());
+        let full_path = |tree: &HashedTree, path: &Path| {
+            tree.full_path(path).unwrap_or(path).to_owned()
+        };
+        // filename, path, hash, datetime. Paths are logged as relative paths,
+        // except for matched files which are logged with their full target path
+        let single = |paths: Vec<(&Path, &Path)>, tree: &HashedTree, path_column: &str| {
+            std::iter::once([columns(&["FILENAME", path_column]), hash_columns.to_owned(), columns(&["DATETIME"])].concat())
+                .chain(paths.into_iter().map(|(logged_path, path)| [
+                    vec![filename(logged_path), logged_path.display().to_string()],
+                    tree.hashes(path).unwrap_or_default().to_vec(),
+                    vec![now_to_string()],
+                ].concat()))
+                .collect::<Vec<_>>()
+        };
+        let matched = self.matched
+            .iter()
+            .chain(self.legacy.iter())
+            .map(|path| (target.full_path(path).unwrap_or(path), path.as_path()))
+            .collect();
+
+        let mut changed = vec![[
+            columns(&["FILENAME", "SOURCEPATH"]),
+            hash_headers(hash_types, "SOURCE"),
+            columns(&["SOURCEMODIFIED", "TARGETPATH"]),
+            hash_headers(hash_types, "TARGET"),
+            columns(&["TARGETMODIFIED"]),
+        ].concat()];
+        for path in self.changed.iter() {
+            let (_, source_modified) = source.file_info(path);
+            let (_, target_modified) = target.file_info(path);
+            changed.push([
+                vec![filename(&full_path(source, path)), full_path(source, path).display().to_string()],
+                source.hashes(path).unwrap_or_default().to_vec(),
+                vec![source_modified.unwrap_or("-".to_owned()), full_path(target, path).display().to_string()],
+                target.hashes(path).unwrap_or_default().to_vec(),
+                vec![target_modified.unwrap_or("-".to_owned())],
+            ].concat());
+        }
+
+        // filename, source path, source hash, target path, target hash, size, modified, datetime
+        let mut corrupt = vec![[
+            columns(&["FILENAME", "SOURCEPATH"]),
+            hash_headers(hash_types, "SOURCE"),
+            columns(&["TARGETPATH"]),
+            hash_headers(hash_types, "TARGET"),
+            columns(&["SIZE", "MODIFIED", "DATETIME"]),
+        ].concat()];
+        for path in self.corrupt.iter() {
+            let (size, modified) = target.file_info(path);
+            corrupt.push([
+                vec![filename(&full_path(target, path)), full_path(source, path).display().to_string()],
+                source.hashes(path).unwrap_or_default().to_vec(),
+                vec![full_path(target, path).display().to_string()],
+                target.hashes(path).unwrap_or_default().to_vec(),
+                vec![
+                    size.map(|n| n.to_string()).unwrap_or_default(),
+                    modified.unwrap_or_default(),
+                    now_to_string(),
+                ],
+            ].concat());
+        }
+
+        // filename, old path, new path, hash, datetime
+        let mut moved = vec![[
+            columns(&["FILENAME", "SOURCEPATH", "TARGETPATH"]),
+            hash_columns.to_owned(),
+            columns(&["DATETIME"]),
+        ].concat()];
+        for (source_path, target_path) in self.moved.iter() {
+            moved.push([
+                vec![
+                    filename(target_path),
+                    source_path.display().to_string(),
+                    target_path.display().to_string(),
+                ],
+                target.hashes(target_path).unwrap_or_default().to_vec(),
+                vec![now_to_string()],
+            ].concat());
+        }
+
+        let log = |category, file_name, rows| ComparisonLog { category, file_name, rows };
+        vec![
+            log("missing", "missing_in_target.csv", single(self.missing.iter().map(|p| (p.as_path(), p.as_path())).collect(), source, "SOURCEPATH")),
+            log("changed", "changed_in_target.csv", changed),
+            log("corrupt", "corrupt_in_target.csv", corrupt),
+            log("ignored", "missing_in_source.csv", single(self.ignored.iter().map(|p| (p.as_path(), p.as_path())).collect(), target, "TARGETPATH")),
+            log("moved", "moved.csv", moved),
+            log("matched", "matched.csv", single(matched, target, "TARGETPATH")),
+        ]
+    }
+
+    /// Returns file counts per category as a JSON object.
+    pub fn totals_json(&self, source: &HashedTree, target: &HashedTree) -> Value {
+        json!({
+            "source": source.hashes.len(),
+            "target": target.hashes.len(),
+            "matched": self.matched.len(),
+            "legacy": self.legacy.len(),
+            "missing": self.missing.len(),
+            "moved": self.moved.len(),
+            "changed": self.changed.len(),
+            "corrupt": self.corrupt.len(),
+            "ignored": self.ignored.len(),
+            "errors": source.errors.len() + target.errors.len(),
+        })
+    }
+
+    /// Returns the report as a single JSON document with totals, hash types,
+    /// and one array per category. Hashes are keyed on hash type,
+    /// changed and corrupt files list hash, size, and modification time for both sides.
+    /// `source` and `target` are the hashed directories, `null` for manifests.
+    pub fn to_json(&self, source: &HashedTree, target: &HashedTree, hash_types: &[HashType]) -> Value {
+        let hashes = |tree: &HashedTree, path: &Path| {
+            hashes_to_json(hash_types, tree.hashes(path).unwrap_or_default())
+        };
+        let full_path = |tree: &HashedTree, path: &Path| {
+            tree.full_path(path).map(|p| p.display().to_string())
+        };
+        let side = |tree: &HashedTree, path: &Path| {
+            let (size, modified) = tree.file_info(path);
+            json!({
+                "path": full_path(tree, path),
+                "hashes": hashes(tree, path),
+                "size": size,
+                "modified": modified,
+            })
+        };
+        let diff = |paths: &[PathBuf]| -> Vec<Value> {
+            paths
+                .iter()
+                .map(|path| json!({
+                    "path": path.display().to_string(),
+                    "source": side(source, path),
+                    "target": side(target, path),
+                }))
+                .collect()
+        };
+        let single = |paths: &[PathBuf], tree: &HashedTree| -> Vec<Value> {
+            paths
+                .iter()
+                .map(|path| json!({
+                    "path": path.display().to_string(),
+                    "full_path": full_path(tree, path),
+                    "hashes": hashes(tree, path),
+                }))
+                .collect()
+        };
+        let errors: Vec<Value> = source.errors
+            .iter()
+            .chain(target.errors.iter())
+            .map(|err| json!({
+                "path": err.path().map(|p| p.display().to_string()),
+                "error": err.to_string(),
+            }))
+            .collect();
+
+        json!({
+            "hash_types": hash_types.iter().map(|h| h.to_string()).collect::<Vec<_>>(),
+            "source": source.root.as_ref().map(|p| p.display().to_string()),
+            "target": target.root.as_ref().map(|p| p.display().to_string()),
+            "totals": self.totals_json(source, target),
+            "matched": single(&self.matched, target),
+            "legacy": single(&self.legacy, target),
+            "missing": single(&self.missing, source),
+            "moved": self.moved
+                .iter()
+                .map(|(source_path, target_path)| json!({
+                    "source_path": source_path.display().to_string(),
+                    "target_path": target_path.display().to_string(),
+                    "hashes": hashes(target, target_path),
+                }))
+                .collect::<Vec<_>>(),
+            "changed": diff(&self.changed),
+            "corrupt": diff(&self.corrupt),
+            "ignored": single(&self.ignored, target),
+            "errors": errors,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tree with one hash per file, with size and modification time as if logged in a manifest.
+    fn tree(files: &[(&str, &str, u64, &str)]) -> HashedTree {
+        HashedTree {
+            root: None,
+            hashes: files
+                .iter()
+                .map(|(path, hash, _, _)| (PathBuf::from(path), (PathBuf::from(path), vec![hash.to_string()])))
+                .collect(),
+            info: Some(files
+                .iter()
+                .map(|(path, _, size, modified)| (PathBuf::from(path), (Some(*size), Some(modified.to_string()))))
+                .collect()),
+            errors: Vec::new(),
+        }
+    }
+
+    fn paths(paths: &[&str]) -> Vec<PathBuf> {
+        paths.iter().map(PathBuf::from).collect()
+    }
+
+    #[test]
+    fn compare_missing_changed_moved() {
+        let source = tree(&[
+            ("a.txt", "aa", 1, "2024-01-01 00:00:00.000"),
+            ("b.txt", "bb", 1, "2024-01-01 00:00:00.000"),
+            ("c.txt", "cc", 1, "2024-01-01 00:00:00.000"),
+            ("d.txt", "dd", 1, "2024-01-01 00:00:00.000"),
+            ("empty1", "00", 0, "2024-01-01 00:00:00.000"),
+        ]);
+        let target = tree(&[
+            ("a.txt", "aa", 1, "2024-01-01 00:00:00.000"),
+            ("b.txt", "b2", 2, "2024-02-01 00:00:00.000"),
+            ("dir/c.txt", "cc", 1, "2024-01-01 00:00:00.000"),
+            ("new.txt", "nn", 1, "2024-01-01 00:00:00.000"),
+            ("empty2", "00", 0, "2024-01-01 00:00:00.000"),
+        ]);
+
+        let report = compare(&source, &target);
+        assert_eq!(report.matched, paths(&["a.txt"]));
+        assert_eq!(report.changed, paths(&["b.txt"]));
+        assert_eq!(report.moved, [(PathBuf::from("c.txt"), PathBuf::from("dir/c.txt"))]);
+        // empty files share a hash, so are never matched as moved
+        assert_eq!(report.missing, paths(&["d.txt", "empty1"]));
+        assert_eq!(report.ignored, paths(&["empty2", "new.txt"]));
+        assert!(report.corrupt.is_empty());
+    }
+
+    #[test]
+    fn compare_moved_with_duplicate_hashes() {
+        let source = tree(&[
+            ("a1", "xx", 1, "2024-01-01 00:00:00.000"),
+            ("a2", "xx", 1, "2024-01-01 00:00:00.000"),
+            ("a3", "yy", 1, "2024-01-01 00:00:00.000"),
+        ]);
+        let target = tree(&[
+            ("b3", "xx", 1, "2024-01-01 00:00:00.000"),
+            ("b1", "xx", 1, "2024-01-01 00:00:00.000"),
+            ("b2", "xx", 1, "2024-01-01 00:00:00.000"),
+        ]);
+
+        // paired in sorted order, the remaining duplicate is new in target
+        let report = compare(&source, &target);
+        assert_eq!(report.moved, [
+            (PathBuf::from("a1"), PathBuf::from("b1")),
+            (PathBuf::from("a2"), PathBuf::from("b2")),
+        ]);
+        assert_eq!(report.missing, paths(&["a3"]));
+        assert_eq!(report.ignored, paths(&["b3"]));
+    }
+
+    #[test]
+    fn compare_corrupt() {
+        let source = tree(&[
+            ("corrupt.txt", "cc", 1, "2024-01-01 00:00:00.000"),
+            ("edited.txt", "ee", 1, "2024-01-01 00:00:00.000"),
+            ("unknown.txt", "uu", 1, "2024-01-01 00:00:00.000"),
+        ]);
+        let mut target = tree(&[
+            ("corrupt.txt", "c2", 1, "2024-01-01 00:00:00.000"),
+            ("edited.txt", "e2", 1, "2024-03-01 00:00:00.000"),
+            ("unknown.txt", "u2", 1, "2024-01-01 00:00:00.000"),
+        ]);
+        // e.g. a manifest without MODIFIED column
+        target.info.as_mut().unwrap().insert(PathBuf::from("unknown.txt"), (Some(1), None));
+
+        // hash changed, size and modification time did not
+        let report = compare(&source, &target);
+        assert_eq!(report.corrupt, paths(&["corrupt.txt"]));
+        assert_eq!(report.changed, paths(&["edited.txt", "unknown.txt"]));
+    }
+}
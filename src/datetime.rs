@@ -1,10 +1,10 @@
-use std::{path::Path, time::{SystemTime, UNIX_EPOCH, SystemTimeError}};
+use std::path::Path;
 
-use time::{format_description, OffsetDateTime, ext::NumericalDuration};
+use time::{format_description, Date, OffsetDateTime, PrimitiveDateTime, ext::NumericalDuration};
 
 /// Formats datetime to string, `YYYY-MM-DD HH:mm:SS.fff`.
 pub fn datetime_to_string(datetime: &OffsetDateTime) -> String {
-    let format = format_description::parse("[year]-[month]-[day] [hour]:[minute]:[second].[subsecond digits:3]")
+    let format = format_description::parse_borrowed::<2>("[year]-[month]-[day] [hour]:[minute]:[second].[subsecond digits:3]")
         .expect("Failed to parse xatetime format");
     datetime.format(&format)
         .expect("Failed to format datetime string")
@@ -14,6 +14,51 @@ pub fn now_to_string() -> std::string::String {
     datetime_to_string(&OffsetDateTime::now_utc())
 }
 
+/// Returns current UTC time as `YYYY-MM-DDTHH-mm-SS`, for use in directory names.
+pub fn now_to_dirname() -> String {
+    let format = format_description::parse_borrowed::<2>("[year]-[month]-[day]T[hour]-[minute]-[second]")
+        .expect("Failed to parse datetime format");
+    OffsetDateTime::now_utc().format(&format)
+        .expect("Failed to format datetime string")
+}
+
+/// Parses an absolute UTC date (`YYYY-MM-DD`) or datetime (`YYYY-MM-DD HH:mm:SS`,
+/// or with `T` as separator), or a duration before now with a unit suffix:
+/// `s` (seconds), `m` (minutes), `h` (hours), `d` (days), or `w` (weeks), e.g. `7d`.
+pub fn parse_datetime(value: &str) -> Result<OffsetDateTime, String> {
+    let value = value.trim();
+
+    if let Some(unit) = value.chars().last().filter(|c| c.is_ascii_alphabetic()) {
+        if let Ok(n) = value[..value.len() - 1].parse::<i64>() {
+            let duration = match unit.to_ascii_lowercase() {
+                's' => n.seconds(),
+                'm' => n.minutes(),
+                'h' => n.hours(),
+                'd' => n.days(),
+                'w' => n.weeks(),
+                _ => return Err(format!("Invalid duration unit '{unit}', expected s, m, h, d, or w")),
+            };
+            return Ok(OffsetDateTime::now_utc() - duration);
+        }
+    }
+
+    let value = value.replacen('T', " ", 1);
+    let datetime = |format: &str| {
+        let format = format_description::parse_borrowed::<2>(format)
+            .expect("Failed to parse datetime format");
+        PrimitiveDateTime::parse(&value, &format)
+    };
+    datetime("[year]-[month]-[day] [hour]:[minute]:[second]")
+        .or_else(|_| datetime("[year]-[month]-[day] [hour]:[minute]"))
+        .or_else(|_| {
+            let format = format_description::parse_borrowed::<2>("[year]-[month]-[day]")
+                .expect("Failed to parse date format");
+            Date::parse(&value, &format).map(|d| d.midnight())
+        })
+        .map(|dt| dt.assume_utc())
+        .map_err(|_| format!("Invalid date or duration '{value}', expected e.g. '2026-01-31', '2026-01-31 12:00:00', or '7d'"))
+}
+
 /// Returns modified datetime for path.
 pub fn datetime_modified(path: &Path) -> Option<OffsetDateTime> {
     match path.metadata() {
@@ -24,12 +69,38 @@ pub fn datetime_modified(path: &Path) -> Option<OffsetDateTime> {
     }
 }
 
-/// Converts `SystemTime` to `time::OffsetDateTime`.
-fn systime2datetime(systime: SystemTime) -> Result<OffsetDateTime, SystemTimeError> {
-    Ok(OffsetDateTime::UNIX_EPOCH + systime.duration_since(UNIX_EPOCH)?)
-}
+#[cfg(test)]
+mod tests {
+    use time::Month;
 
-/// Converts seconds since Unix epoch start of January 1, 1970 to `time::OffsetDateTime`.
-fn unixseconds2datetime(unix_seconds: i64) -> Result<OffsetDateTime, SystemTimeError> {
-    Ok(OffsetDateTime::UNIX_EPOCH + unix_seconds.seconds())
-}
\ No newline at end of file
+    use super::*;
+
+    #[test]
+    fn parse_absolute() {
+        let date = Date::from_calendar_date(2026, Month::January, 31).unwrap();
+        let expected = |h, m, s| date.with_hms(h, m, s).unwrap().assume_utc();
+
+        assert_eq!(parse_datetime("2026-01-31"), Ok(expected(0, 0, 0)));
+        assert_eq!(parse_datetime("2026-01-31 12:30"), Ok(expected(12, 30, 0)));
+        assert_eq!(parse_datetime("2026-01-31 12:30:15"), Ok(expected(12, 30, 15)));
+        assert_eq!(parse_datetime("2026-01-31T12:30:15"), Ok(expected(12, 30, 15)));
+        assert!(parse_datetime("2026-02-30").is_err());
+        assert!(parse_datetime("31/01/2026").is_err());
+    }
+
+    #[test]
+    fn parse_duration() {
+        for (value, seconds) in [("30s", 30), ("5m", 300), ("2h", 7200), ("7d", 604_800), ("1W", 604_800)] {
+            let expected = OffsetDateTime::now_utc() - seconds.seconds();
+            let parsed = parse_datetime(value).unwrap();
+            assert!((parsed - expected).abs() < 5.seconds(), "{value}");
+        }
+        assert!(parse_datetime("7y").is_err());
+    }
+
+    #[test]
+    fn datetime_round_trip() {
+        let datetime = parse_datetime("2026-01-31 12:30:15").unwrap();
+        assert_eq!(datetime_to_string(&datetime), "2026-01-31 12:30:15.000");
+    }
+}
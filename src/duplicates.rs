@@ -0,0 +1,141 @@
+use std::path::{Path, PathBuf};
+
+use crate::{
+    errors::ChksumError,
+    hash::{hash2path, hash_dir, hash_files, HashContext, HashOptions, HashType, Hashes},
+    walk::WalkOptions,
+};
+
+/// Files with identical hashes.
+#[derive(Debug, Clone)]
+pub struct DuplicateGroup {
+    /// Hash shared by all files. Space separated for several hash types.
+    pub hash: String,
+    /// Full paths, sorted.
+    pub paths: Vec<PathBuf>,
+}
+
+/// Result of `find_duplicates`.
+#[derive(Debug, Clone, Default)]
+pub struct Duplicates {
+    /// Files found, including those that could not be read.
+    pub files: usize,
+    /// Files sharing a partial hash with at least one other file, hashed in full.
+    pub hashed_in_full: usize,
+    /// Files with identical full hashes, sorted on first path.
+    pub groups: Vec<DuplicateGroup>,
+}
+
+/// Options for `find_duplicates`.
+#[derive(Debug, Clone)]
+pub struct DuplicateOptions {
+    /// Options for walking the directory.
+    pub walk_options: WalkOptions,
+    /// Hash type for files that remain after the quick check.
+    pub hash_type: HashType,
+    /// Hash type for the quick check.
+    pub partial_hash_type: HashType,
+    /// Number of bytes to hash from the start of each file for the quick check.
+    pub partial_hash_size: usize,
+    /// Options for hashing files in full. `partial` is ignored.
+    pub hash_options: HashOptions,
+}
+
+impl Default for DuplicateOptions {
+    fn default() -> Self {
+        Self {
+            walk_options: WalkOptions::default(),
+            hash_type: HashType::Blake3,
+            partial_hash_type: HashType::Blake3,
+            partial_hash_size: 1000,
+            hash_options: HashOptions::default(),
+        }
+    }
+}
+
+/// Returns full paths for files that share their hash with at least one other file.
+/// Used to prune unique files after a partial hash quick check.
+pub fn prune_unique(hashes: &Hashes) -> Vec<PathBuf> {
+    hash2path(hashes, true)
+        .into_values()
+        .flatten()
+        .collect()
+}
+
+/// Groups files with identical hashes. Unique files are excluded.
+/// Groups are sorted on first path.
+pub fn duplicate_groups(hashes: &Hashes) -> Vec<DuplicateGroup> {
+    let mut groups: Vec<DuplicateGroup> = hash2path(hashes, true)
+        .into_iter()
+        .map(|(hash, paths)| DuplicateGroup { hash, paths })
+        .collect();
+    groups.sort_by(|a, b| a.paths.cmp(&b.paths));
+    groups
+}
+
+/// Finds duplicate files in `dir`. All files are first hashed partially
+/// while `dir` is walked, then only files sharing a partial hash are hashed in full.
+///
+/// See `HashContext` for `context`. `on_hashed` is called for partial
+/// and full hashes, the cache is only used for full hashes.
+pub fn find_duplicates(
+    dir: &Path,
+    options: &DuplicateOptions,
+    context: HashContext,
+) -> Result<Duplicates, ChksumError> {
+    let HashContext { mut cache, mut errors, on_hashed } = context;
+    let error_count = errors.as_ref().map(|errors| errors.len()).unwrap_or_default();
+    let partial_options = HashOptions {
+        partial: Some(options.partial_hash_size),
+        ..options.hash_options.clone()
+    };
+    // partial hashes are never cached, the cache is only passed on so that its file is not hashed
+    let partial_hashes = hash_dir(
+        dir,
+        &options.walk_options,
+        std::slice::from_ref(&options.partial_hash_type),
+        &partial_options,
+        HashContext { cache: cache.as_deref_mut(), errors: errors.as_deref_mut(), on_hashed },
+    )?;
+    let files = partial_hashes.len() + errors.as_ref().map(|errors| errors.len()).unwrap_or_default() - error_count;
+
+    let pruned_paths = prune_unique(&partial_hashes);
+    let hashes = hash_files(
+        &pruned_paths,
+        std::slice::from_ref(&options.hash_type),
+        &options.hash_options,
+        None,
+        HashContext { cache, errors, on_hashed },
+    )?;
+
+    Ok(Duplicates {
+        files,
+        hashed_in_full: pruned_paths.len(),
+        groups: duplicate_groups(&hashes),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_duplicates_after_quick_check() {
+        let root = std::env::temp_dir().join(format!("chksum_duplicates_test_{}", std::process::id()));
+        std::fs::create_dir_all(root.join("dir")).unwrap();
+        std::fs::write(root.join("a.txt"), "same start, same end").unwrap();
+        std::fs::write(root.join("dir/b.txt"), "same start, same end").unwrap();
+        // shares the partial hash, but not the full hash
+        std::fs::write(root.join("c.txt"), "same start, other end").unwrap();
+        std::fs::write(root.join("d.txt"), "unique").unwrap();
+
+        let options = DuplicateOptions { partial_hash_size: 10, ..DuplicateOptions::default() };
+        let duplicates = find_duplicates(&root, &options, HashContext::default()).unwrap();
+        assert_eq!(duplicates.files, 4);
+        assert_eq!(duplicates.hashed_in_full, 3);
+        assert_eq!(duplicates.groups.len(), 1);
+        assert_eq!(duplicates.groups[0].paths, [root.join("a.txt"), root.join("dir/b.txt")]);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -1,4 +1,6 @@
-use std::{fmt, path::{PathBuf, StripPrefixError}};
+use std::{fmt, path::{Path, PathBuf, StripPrefixError}};
+
+use crate::{datetime::now_to_string, files::filename_to_string};
 
 #[derive(Debug)]
 pub enum ChksumError {
@@ -8,7 +10,26 @@ pub enum ChksumError {
     PathStripPrefixError(StripPrefixError),
     OpenFileFailed((PathBuf, std::io::Error)),
     ReadFileFailed((PathBuf, std::io::Error)),
-    FileDoesNotExist(PathBuf)
+    FileDoesNotExist(PathBuf),
+    ManifestParseError((PathBuf, String)),
+    UnknownHashType(String),
+    /// Source and target manifests use different hash types.
+    HashTypeMismatch((String, String)),
+}
+
+impl ChksumError {
+    /// Returns the path that caused the error, if any.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            ChksumError::PartialHashFailed((path, _))
+            | ChksumError::HashFailed((path, _))
+            | ChksumError::OpenFileFailed((path, _))
+            | ChksumError::ReadFileFailed((path, _))
+            | ChksumError::FileDoesNotExist(path)
+            | ChksumError::ManifestParseError((path, _)) => Some(path),
+            _ => None,
+        }
+    }
 }
 
 impl std::error::Error for ChksumError {}
@@ -28,6 +49,11 @@ impl fmt::Display for ChksumError {
                 "Failed to read file '{}': {err}",
                 path.display()),
             ChksumError::FileDoesNotExist(path) => write!(f, "File does not exist '{}'", path.display()),
+            ChksumError::ManifestParseError((path, msg)) => write!(f, "Failed to parse manifest '{}': {msg}", path.display()),
+            ChksumError::UnknownHashType(name) => write!(f, "Unknown hash type '{name}'"),
+            ChksumError::HashTypeMismatch((source, target)) => write!(
+                f,
+                "Source manifest ({source}) and target manifest ({target}) use different hash algorithms"),
         }
     }
 }
@@ -41,7 +67,7 @@ impl From<std::io::Error> for ChksumError {
 
 impl From<ChksumError> for std::io::Error {
     fn from(value: ChksumError) -> std::io::Error {
-        std::io::Error::new(std::io::ErrorKind::Other, value)
+        std::io::Error::other(value)
     }
 }
 
@@ -50,3 +76,17 @@ impl From<StripPrefixError> for ChksumError {
         ChksumError::PathStripPrefixError(value)
     }
 }
+
+/// Returns errors as log rows (`FILENAME`, `PATH`, `ERROR`, `DATETIME`), including header.
+pub fn errors_to_log(errors: &[&ChksumError]) -> Vec<Vec<String>> {
+    let mut log = vec![vec!["FILENAME".to_owned(), "PATH".to_owned(), "ERROR".to_owned(), "DATETIME".to_owned()]];
+    for err in errors.iter() {
+        log.push(vec![
+            err.path().and_then(filename_to_string).unwrap_or_default(),
+            err.path().map(|p| p.display().to_string()).unwrap_or_default(),
+            err.to_string(),
+            now_to_string()
+        ]);
+    }
+    log
+}
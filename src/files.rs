@@ -1,292 +1,283 @@
 use std::{
-    collections::HashMap,
-    ffi::{OsStr, OsString},
-    fs::File,
-    io::Write,
+    env::current_dir,
+    fs::{create_dir_all, File},
+    io::{IsTerminal, Write},
     path::{Path, PathBuf},
+    sync::Mutex,
 };
 
 use filetime::FileTime;
 use time::{ext::NumericalDuration, OffsetDateTime};
-use walkdir::{DirEntry, WalkDir};
-
-/// Passed to `Walkdir::filter`. Returns `true` if `DirEntry`:
-/// - is/is not hidden, (`include_hidden` - currently only Unix/Linux)
-/// - does not contains a component/dir name that equeals `ignore_dir`
-/// - has a file extension specified in `include_ext`
-/// - does no have a file extension specified in `exclude_ext`
-pub fn include(
-    direntry: &DirEntry,
-    include_hidden: bool,
-    follow_links: bool,
-    include_ext: &[String],
-    exclude_ext: &[String],
-    exclude_dir: &[String],
-) -> bool {
-
-    // WalkDir should have already followed/resolved
-    // paths if follow_links is set, but when not set
-    // symlinks need to be ignored
-    if direntry.path_is_symlink() && !follow_links {
-        return false;
-    }
-
-    if direntry.path().is_dir() {
-        return false;
-    }
 
-    if contains_dir(direntry, exclude_dir) {
-        return false;
-    }
+use crate::{
+    datetime::{datetime_modified, datetime_to_string, now_to_string},
+    hash::{hash_headers, HashType},
+};
 
-    let is_hidden = direntry
-        .file_name()
-        .to_str()
-        .map(|n| n.starts_with(".")) // unix/linux only, windows has attributes that need checking
-        .unwrap_or(false);
-    if !include_hidden && is_hidden {
-        return false;
+/// Asks user to confirm `msg` on stderr. Returns `default` without asking
+/// if stdin is not a terminal (e.g. cron) or is closed.
+pub fn confirm(msg: &str, default: bool) -> std::io::Result<bool> {
+    let answer = if default { "y" } else { "n" };
+    if !std::io::stdin().is_terminal() {
+        eprintln!("{msg} (y/n): {answer} (stdin is not a terminal)");
+        return Ok(default);
     }
 
-    if let Some(ext) = direntry.path().extension() {
-        let ext_str = ext.to_string_lossy().to_ascii_lowercase();
-        if !include_ext.is_empty() {
-            return include_ext.contains(&ext_str);
-        }
-
-        if !exclude_ext.is_empty() {
-            return !exclude_ext.contains(&ext_str);
+    loop {
+        eprint!("{msg} (y/n): ");
+        std::io::stderr().flush()?;
+        let mut input = String::new();
+        if std::io::stdin().read_line(&mut input)? == 0 {
+            eprintln!("{answer}");
+            return Ok(default);
         }
-    } else {
-        // Check to ignore files without extension whenever
-        // include extentions are specified
-        if !include_ext.is_empty() {
-            return false;
+        match input.trim().to_lowercase().as_str() {
+            "y" | "yes" => return Ok(true),
+            "n" | "no" => return Ok(false),
+            _ => eprintln!("(!) Enter y/yes or n/no"),
         }
     }
+}
 
-    true
+/// What to do if a file about to be written already exists.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Overwrite {
+    /// Ask user. Existing files are kept if stdin is not a terminal.
+    #[default]
+    Prompt,
+    /// Overwrite without asking.
+    Always,
+    /// Keep existing files without asking.
+    Never,
 }
 
-/// Passed to `Walkdir::filter`. Returns `Some(PATH)` if `DirEntry`:
-/// - is/is not hidden, (`include_hidden` - currently only Unix/Linux)
-/// - does not contains a component/dir name that equeals `ignore_dir`
-/// - has a file extension specified in `include_ext`
-/// - does not have a file extension specified in `exclude_ext`
-pub fn include2(
-    direntry: &DirEntry,
-    include_hidden: bool,
-    follow_links: bool,
-    include_ext: &[String],
-    exclude_ext: &[String],
-    exclude_dir: &[String],
-// ) -> bool {
-) -> Option<PathBuf> {
-
-    // WalkDir should have already followed/resolved
-    // paths if follow_links is set, but when not set
-    // symlinks need to be ignored
-    if direntry.path_is_symlink() && !follow_links {
-        return None
+/// Returns `true` if `outpath` does not exist, or may be overwritten, see `Overwrite`.
+pub fn may_write(outpath: &Path, overwrite: Overwrite) -> std::io::Result<bool> {
+    if !outpath.exists() {
+        return Ok(true);
     }
-
-    if direntry.path().is_dir() {
-        return None
+    match overwrite {
+        Overwrite::Always => Ok(true),
+        Overwrite::Never => Ok(false),
+        Overwrite::Prompt => {
+            let msg = format!("(!) '{}' already exists. Overwrite?", outpath.display());
+            confirm(&msg, false)
+        }
     }
+}
 
-    if contains_dir(direntry, exclude_dir) {
-        return None
+/// Write file to disk. Returns `false` if `outpath` exists and was kept,
+/// see `Overwrite`.
+pub fn writefile(content: &str, outpath: &Path, overwrite: Overwrite) -> std::io::Result<bool> {
+    if !may_write(outpath, overwrite)? {
+        return Ok(false);
     }
 
-    let is_hidden = direntry
-        .file_name()
-        .to_str()
-        .map(|n| n.starts_with(".")) // unix/linux only, windows has attributes that need checking
-        .unwrap_or(false);
-    if !include_hidden && is_hidden {
-        return None
-    }
+    let mut outfile = File::create(outpath)?;
+    outfile.write_all(content.as_bytes())?;
 
-    if let Some(ext) = direntry.path().extension() {
-        let ext_str = ext.to_string_lossy().to_ascii_lowercase();
-        if !include_ext.is_empty() {
-            if include_ext.contains(&ext_str) {
-                return Some(direntry.path().to_owned());
-            }
-        }
+    Ok(true)
+}
 
-        if !exclude_ext.is_empty() {
-            if !exclude_ext.contains(&ext_str) {
-                return Some(direntry.path().to_owned());
-            }
+/// Returns log directory. Defaults to `chksum_logs` in the current directory,
+/// which is created if it does not exist. A custom log directory must exist.
+/// If `run_dir` is set, logs go in a sub-directory with that name, which is created.
+pub fn log_dir(custom_dir: Option<&PathBuf>, run_dir: Option<&str>) -> std::io::Result<PathBuf> {
+    let dir = match custom_dir {
+        Some(d) => d.to_owned(),
+        None => {
+            let dir = current_dir()?.join("chksum_logs");
+            create_dir_all(&dir)?;
+            dir
         }
-    } else {
-        // Check to ignore files without extension whenever
-        // include extentions are specified
-        if !include_ext.is_empty() {
-            return None
+    };
+    match run_dir {
+        Some(name) => {
+            let dir = dir.join(name);
+            create_dir_all(&dir)?;
+            Ok(dir)
         }
+        None => Ok(dir),
     }
+}
 
-    Some(direntry.path().to_owned())
+/// Options for writing logs as CSV.
+#[derive(Debug, Clone)]
+pub struct CsvOptions {
+    /// Field delimiter, `,` by default.
+    pub delimiter: u8,
+    /// Quote all fields, not only those containing the delimiter, quotes, or line breaks.
+    pub quote_all: bool,
+    /// Prefix with a UTF-8 byte order mark, e.g. for Excel.
+    pub bom: bool,
 }
 
-fn contains_dir(direntry: &DirEntry, dirs: &[String]) -> bool {
-    for dir in dirs.iter() {
-        if direntry
-            .path()
-            .components()
-            .any(|c| c == std::path::Component::Normal(OsStr::new(dir)))
-        {
-            return true;
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self {
+            delimiter: b',',
+            quote_all: false,
+            bom: false,
         }
     }
-    false
 }
 
-/// Compile paths, but halts on errors.
-pub fn paths(
-    dir: &Path,
-    include_hidden: bool,
-    follow_links: bool,
-    exclude_dir: &[String],
-    include_ext: &[String],
-    exclude_ext: &[String],
-    ignore_error: bool
-) -> std::io::Result<Vec<PathBuf>> {
-    WalkDir::new(&dir)
-        // if follow symlinks = true, WalkDir must yield the followed path
-        // or chksum will attempt to open the symlink instead of the target
-        // path which will raise an error
-        .follow_links(follow_links)
-        .into_iter()
-        .filter_map(|direntry|
-            // Filter out dirs/dirnames/extensions
-            match direntry {
-                Ok(entry) => {
-                    // include2(&entry, include_hidden, follow_links, include_ext, exclude_ext, exclude_dir)
-                    if include(&entry, include_hidden, follow_links, include_ext, exclude_ext, exclude_dir) {
-                        Some(Ok(entry.path().to_owned()))
-                    } else {
-                        None
-                    }
-                },
-                Err(e) => {
-                    // Whether to continue on error or not
-                    // E.g. when a socket is encountered
-                    match ignore_error {
-                        true => None,
-                        false => Some(Err(e.into()))
-                    }
-                },
-            }
-                // must check follow links again here, since if not set,
-                // the link will raise error later on
-        )
-        .collect::<Result<Vec<_>, _>>()
-}
+impl CsvOptions {
+    fn writer(&self) -> csv::WriterBuilder {
+        let mut builder = csv::WriterBuilder::new();
+        builder
+            .delimiter(self.delimiter)
+            .quote_style(if self.quote_all { csv::QuoteStyle::Always } else { csv::QuoteStyle::Necessary })
+            .terminator(csv::Terminator::CRLF)
+            .flexible(true);
+        builder
+    }
 
-pub fn has_extension(path: &Path, ext: &str) -> bool {
-    path.extension().map(|s| s.to_ascii_lowercase()) == Some(OsString::from(&ext.to_lowercase()))
-}
+    /// Returns `rows` as RFC 4180 CSV with CRLF line breaks.
+    /// Rows may differ in length.
+    pub fn to_csv(&self, rows: &[Vec<String>]) -> std::io::Result<String> {
+        let mut writer = self.writer().from_writer(Vec::new());
+        for row in rows.iter() {
+            writer.write_record(row)?;
+        }
+        let bytes = writer.into_inner().map_err(|err| err.into_error())?;
 
-pub fn has_extension_any(path: &Path, exts: &[&str]) -> bool {
-    exts.iter().any(|ext| has_extension(path, ext))
+        let mut csv = String::from(if self.bom { "\u{feff}" } else { "" });
+        csv.push_str(&String::from_utf8_lossy(&bytes));
+        Ok(csv)
+    }
 }
 
-pub fn confirm(msg: &str) -> std::io::Result<bool> {
-    print!("{msg} (y/n): ");
-    std::io::stdout().flush()?;
-    let mut overwrite = String::new();
-    std::io::stdin().read_line(&mut overwrite)?;
-
-    loop {
-        match overwrite.to_lowercase().trim_matches('\n') {
-            "y" | "yes" => return Ok(true),
-            "n" | "no" => return Ok(false),
-            _ => {
-                println!("(!) Enter y/yes or n/no")
-            }
-        }
-    }
+/// CSV log written one row at a time, e.g. while files are being hashed.
+/// Rows can be appended from several threads.
+#[derive(Debug)]
+pub struct CsvLog {
+    path: PathBuf,
+    /// Writer and first error when writing.
+    inner: Mutex<(csv::Writer<File>, Option<csv::Error>)>,
 }
 
-/// Write file to disk, prompt user if target file exists
-pub fn writefile(content: &String, outpath: &Path) -> std::io::Result<bool> {
-    if outpath.exists() {
-        let msg = format!("(!) '{}' already exists. Overwrite?", outpath.display());
-        if confirm(&msg)? == false {
-            return Ok(false);
+impl CsvLog {
+    /// Creates log at `path` and writes `header`.
+    /// Returns `None` if `path` exists and was kept, see `Overwrite`.
+    pub fn create(
+        path: &Path,
+        header: &[String],
+        options: &CsvOptions,
+        overwrite: Overwrite,
+    ) -> std::io::Result<Option<Self>> {
+        if !may_write(path, overwrite)? {
+            return Ok(None);
         }
-    }
+        let mut file = File::create(path)?;
+        if options.bom {
+            file.write_all("\u{feff}".as_bytes())?;
+        }
+        let mut writer = options.writer().from_writer(file);
+        writer.write_record(header)?;
 
-    let mut outfile = File::create(&outpath)?;
-    outfile.write_all(content.as_bytes())?;
+        Ok(Some(Self {
+            path: path.to_owned(),
+            inner: Mutex::new((writer, None)),
+        }))
+    }
 
-    Ok(true)
-}
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
 
-/// Returns file count per extension as a hashmap: key: file extension, value: count.
-/// `min_count` is the minimum file count that should be represented in the output,
-///  e.g. if set to 3, any occurrence below 3 will be filtered out.
-pub fn file_count(
-    paths: &[PathBuf],
-    min_count: Option<usize>,
-    case_sensitive: bool,
-) -> Vec<(std::string::String, usize)> {
-    let mut extcount: HashMap<String, usize> = HashMap::new();
-
-    for path in paths.iter() {
-        if path.is_file() {
-            match path.extension() {
-                Some(ext) => match case_sensitive {
-                    true => {
-                        *extcount
-                            .entry(ext.to_string_lossy().to_string())
-                            .or_default() += 1
-                    }
-                    false => {
-                        *extcount
-                            .entry(ext.to_ascii_lowercase().to_string_lossy().to_string())
-                            .or_default() += 1
-                    }
-                },
-                None => *extcount.entry(String::from("<NO FILE EXT>")).or_default() += 1,
+    /// Appends `row`. Errors are returned by `finish`, rows after an error are skipped.
+    pub fn append(&self, row: &[String]) {
+        let mut inner = self.inner.lock().expect("Failed to lock log");
+        if inner.1.is_none() {
+            if let Err(err) = inner.0.write_record(row) {
+                inner.1 = Some(err);
             }
         }
     }
 
-    let mut extsorted: Vec<(String, usize)> =
-        extcount.iter().map(|(e, i)| (e.to_owned(), *i)).collect();
-
-    if let Some(min) = min_count {
-        extsorted = extsorted
-            .into_iter()
-            .filter(|(_, size)| size > &(min - 1))
-            .collect();
+    /// Flushes the log to disk. Returns the first error when writing, if any.
+    pub fn finish(self) -> std::io::Result<()> {
+        let (mut writer, error) = self.inner.into_inner().expect("Failed to lock log");
+        match error {
+            Some(err) => Err(err.into()),
+            None => writer.flush(),
+        }
     }
+}
 
-    extsorted.sort_by_key(|(_, size)| *size);
+/// Returns the delimiter in a log header. For chksum logs this is the character
+/// following the first column (`FILENAME`), so that any delimiter set via `--delimiter`
+/// is detected. Otherwise the most frequent of tab, comma, and semicolon.
+/// Logs written by earlier versions are tab-separated.
+pub fn detect_delimiter(header: &str) -> u8 {
+    let first_column = header.strip_prefix('"').unwrap_or(header).strip_prefix("FILENAME");
+    if let Some(&delimiter) = first_column.and_then(|c| c.strip_prefix('"').unwrap_or(c).as_bytes().first()) {
+        return delimiter;
+    }
 
-    extsorted
+    // ties resolve to the last delimiter
+    [b';', b'\t', b',']
+        .into_iter()
+        .max_by_key(|d| header.bytes().filter(|b| b == d).count())
+        .unwrap_or(b',')
 }
 
 pub fn filename_to_string(path: &Path) -> Option<String> {
-    if let Some(filename) = path.file_name() {
-        Some(filename.to_string_lossy().to_string())
-    } else {
-        None
-    }
+    path.file_name().map(|filename| filename.to_string_lossy().to_string())
 }
 
 pub fn fileext_to_string(path: &Path) -> Option<String> {
-    if let Some(fileext) = path.extension() {
-        Some(fileext.to_string_lossy().to_string())
-    } else {
-        None
+    path.extension().map(|fileext| fileext.to_string_lossy().to_string())
+}
+
+/// File size in bytes and modification time as `YYYY-MM-DD HH:mm:SS.fff` (UTC).
+pub type FileInfo = (Option<u64>, Option<String>);
+
+/// Returns file size in bytes and modification time as `YYYY-MM-DD HH:mm:SS.fff` (UTC),
+/// in the same form as the `SIZE` and `MODIFIED` log columns.
+pub fn file_info(path: &Path) -> FileInfo {
+    (
+        path.metadata().ok().map(|m| m.len()),
+        datetime_modified(path).map(|dt| datetime_to_string(&dt)),
+    )
+}
+
+/// Returns `true` if size and modification time are known and identical for both files.
+/// For files with differing hashes this suggests silent corruption (bit rot) rather than an edit.
+pub fn is_corrupt(info1: &FileInfo, info2: &FileInfo) -> bool {
+    match (info1, info2) {
+        ((Some(size1), Some(modified1)), (Some(size2), Some(modified2))) => size1 == size2 && modified1 == modified2,
+        _ => false,
     }
 }
 
+/// Returns the header for a checksum log, see `checksum_row`.
+/// `path_column` names the full path column, e.g. `SOURCEPATH`.
+pub fn checksum_header(path_column: &str, hash_types: &[HashType]) -> Vec<String> {
+    [
+        ["FILENAME", path_column, "RELATIVEPATH", "SIZE", "MODIFIED"].map(String::from).to_vec(),
+        hash_headers(hash_types, ""),
+        vec!["DATETIME".to_owned()],
+    ].concat()
+}
+
+/// Returns a checksum log row: `FILENAME`, `PATH`, `RELATIVEPATH`, `SIZE`, `MODIFIED`,
+/// one column per hash, and `DATETIME`.
+pub fn checksum_row(path: &Path, relative_path: &Path, (size, modified): FileInfo, hashes: &[String]) -> Vec<String> {
+    [
+        vec![
+            filename_to_string(path).unwrap_or("FILENAME ERROR".to_owned()),
+            path.display().to_string(),
+            relative_path.display().to_string(),
+            size.map(|n| n.to_string()).unwrap_or_default(),
+            modified.unwrap_or_default(),
+        ],
+        hashes.to_vec(),
+        vec![now_to_string()],
+    ].concat()
+}
+
 #[derive(Debug, PartialEq)]
 pub enum LogLevel {
     Normal,
@@ -321,8 +312,6 @@ pub fn file_stats(
 ) -> std::io::Result<(Option<String>, u64, Option<OffsetDateTime>, OffsetDateTime)> {
     let metadata = path.metadata()?;
 
-    // let ctime = systime2datetime(metadata.created()?).map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
-    // let mtime = systime2datetime(metadata.modified()?).map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
     let ctime = FileTime::from_creation_time(&metadata)
         .map(|ft| OffsetDateTime::UNIX_EPOCH + ft.unix_seconds().seconds());
     let mtime = OffsetDateTime::UNIX_EPOCH
@@ -334,28 +323,41 @@ pub fn file_stats(
         fileext_to_string(path),
         metadata.len(),
         ctime,
-        mtime, // systime2datetime(metadata.created()?).map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?,
-               // systime2datetime(metadata.modified()?).map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?,
+        mtime,
     ))
 }
 
-pub fn open_file(path: &Path) -> std::io::Result<Option<File>> {
-    match std::fs::File::open(path) {
-        Ok(f) => Ok(Some(f)),
-        Err(err) => match err.kind() {
-            // attempt to ignore operations on sockets on *nix
-            std::io::ErrorKind::Other => {
-                if let Some(os_err) = err.raw_os_error() {
-                    if os_err == 102 {
-                        Ok(None)
-                    } else {
-                        Err(err)
-                    }
-                } else {
-                    Err(err)
-                }
-            }
-            _ => Err(err),
-        },
+/// Hints that `file` will be read sequentially, so that the kernel reads ahead
+/// more aggressively (`posix_fadvise` with `POSIX_FADV_SEQUENTIAL`).
+/// Only a hint, errors are ignored. No effect on platforms other than Linux.
+pub fn advise_sequential(file: &File) {
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::fd::AsRawFd;
+        // Safety: the file descriptor is valid for the lifetime of `file`
+        unsafe { libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_SEQUENTIAL) };
+    }
+    #[cfg(not(target_os = "linux"))]
+    let _ = file;
+}
+
+/// Asks the kernel to drop cached pages for `path` (`posix_fadvise` with `POSIX_FADV_DONTNEED`),
+/// so that the next read is from disk. Pages that are not yet written to disk are kept.
+/// Returns `false` if not supported, i.e. on platforms other than Linux.
+pub fn drop_cached(path: &Path) -> std::io::Result<bool> {
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::fd::AsRawFd;
+        let file = File::open(path)?;
+        // Safety: the file descriptor is valid for the lifetime of `file`
+        match unsafe { libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_DONTNEED) } {
+            0 => Ok(true),
+            errno => Err(std::io::Error::from_raw_os_error(errno)),
+        }
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = path;
+        Ok(false)
     }
 }
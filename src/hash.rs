@@ -1,88 +1,420 @@
+use blake2::Blake2b512;
 use md5::Md5;
 use rayon::iter::IntoParallelRefMutIterator;
-use rayon::prelude::{IntoParallelRefIterator, ParallelIterator};
-use sha2::{Digest, Sha256};
+use rayon::prelude::{ParallelBridge, ParallelIterator, ParallelSlice};
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha512};
+use sha3::Sha3_256;
 use std::io::copy;
+use serde_json::{json, Map, Value};
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet, VecDeque},
     fs::File,
-    io::{Cursor, Read},
+    io::{Cursor, ErrorKind, Read, Write},
     path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::sync_channel,
+        Mutex,
+    },
+    time::{Duration, Instant},
 };
-use time::OffsetDateTime;
+use xxhash_rust::xxh3::Xxh3;
 
-use crate::{datetime::datetime_to_string, errors::ChksumError};
+use crate::{
+    cache::{FileMeta, HashCache},
+    errors::ChksumError,
+    files::advise_sequential,
+    walk::{walk, WalkOptions},
+};
+
+/// Hashes keyed on relative path, value: `(<FULL_PATH>, [HASH, ...])`,
+/// with one hash per hash type.
+pub type Hashes = HashMap<PathBuf, (PathBuf, Vec<String>)>;
+
+/// Passed to `on_hashed` in `hash_files` for each file
+/// as soon as it has been hashed, found in the cache, or failed to hash.
+#[derive(Debug)]
+pub struct HashEvent<'a> {
+    pub path: &'a Path,
+    /// Number of bytes hashed, or file size for cached hashes.
+    pub size: Option<u64>,
+    /// One hash per hash type. `None` on error.
+    pub hashes: Option<&'a [String]>,
+    /// Time spent hashing, zero for cached hashes.
+    pub duration: Duration,
+    pub cached: bool,
+    pub error: Option<&'a ChksumError>,
+}
+
+impl HashEvent<'_> {
+    /// Returns the event as a JSON object with hashes keyed on hash type.
+    pub fn to_json(&self, hashtypes: &[HashType]) -> Value {
+        json!({
+            "event": "file",
+            "path": self.path.display().to_string(),
+            "size": self.size,
+            "hashes": self.hashes.map(|hashes| hashes_to_json(hashtypes, hashes)),
+            "duration_ms": self.duration.as_secs_f64() * 1000.0,
+            "cached": self.cached,
+            "error": self.error.map(|err| err.to_string()),
+        })
+    }
+}
+
+/// Callback for `hash_files`. Called from several threads.
+pub type OnHashed<'a> = &'a (dyn Fn(&HashEvent) + Sync);
+
+/// Optional state for `hash_files`, `hash_dir`, and `hash_stream`.
+#[derive(Default)]
+pub struct HashContext<'a> {
+    /// Files with unchanged metadata are not re-hashed,
+    /// and the cache is updated with new hashes. Partial hashes are never cached.
+    pub cache: Option<&'a mut HashCache>,
+    /// Files that fail to hash, and errors while walking (e.g. unreadable directories),
+    /// are added here and skipped. If `None` the first error is returned.
+    pub errors: Option<&'a mut Vec<ChksumError>>,
+    /// Called for each file as soon as it is done, e.g. to stream progress.
+    pub on_hashed: Option<OnHashed<'a>>,
+}
+
+/// Returns hashes as a JSON object keyed on hash type, e.g. `{"BLAKE3": "..."}`.
+pub fn hashes_to_json(hashtypes: &[HashType], hashes: &[String]) -> Value {
+    hashtypes
+        .iter()
+        .zip(hashes)
+        .map(|(hashtype, hash)| (hashtype.to_string(), Value::from(hash.as_str())))
+        .collect::<Map<String, Value>>()
+        .into()
+}
+
+/// Returns `hash` as encoded by chksum up to 2024-11-06, which lowercased digest bytes
+/// in the ASCII upper case range (`0x41`-`0x5A`) before hex encoding.
+/// Returns `None` if `hash` is not hex or does not contain any such byte.
+pub fn legacy_digest(hash: &str) -> Option<String> {
+    if !hash.len().is_multiple_of(2) || !hash.is_ascii() {
+        return None;
+    }
+    let bytes = (0..hash.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hash[i..i + 2], 16))
+        .collect::<Result<Vec<u8>, _>>()
+        .ok()?;
+    if !bytes.iter().any(|b| b.is_ascii_uppercase()) {
+        return None;
+    }
+    Some(bytes.iter().map(|b| format!("{:02x}", b.to_ascii_lowercase())).collect())
+}
+
+/// Returns `true` if `logged` only matches `actual` when read as legacy digests,
+/// i.e. if written by chksum up to 2024-11-06, see `legacy_digest`.
+pub fn is_legacy_match(logged: &[String], actual: &[String]) -> bool {
+    logged != actual
+        && logged.len() == actual.len()
+        && logged
+            .iter()
+            .zip(actual)
+            .all(|(l, a)| l == a || legacy_digest(a).as_ref() == Some(l))
+}
+
+/// Walked paths are passed from the walker to the hashing threads in `hash_dir`
+/// through a channel of this size, so that memory use does not grow with
+/// the number of files if walking is faster than hashing.
+pub const PIPELINE_CAPACITY: usize = 4096;
+
+/// Default for `HashOptions::parallel_above`, in bytes.
+pub const PARALLEL_HASH_ABOVE: u64 = 128_000_000;
+
+/// Default for `HashOptions::buffer_size`, in bytes.
+pub const BUFFER_SIZE: usize = 1 << 20;
+
+/// Read buffers for `IoStrategy::Buffered` are aligned to, and a multiple of, this size.
+pub const PAGE_SIZE: usize = 4096;
+
+/// How files are read for hashing. Digests are the same for all strategies.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum IoStrategy {
+    /// Reads via `std::io::copy` with its default buffer.
+    /// Files above `HashOptions::parallel_above` are read as for `Buffered`.
+    #[default]
+    Auto,
+    /// Reads into a page aligned buffer of `HashOptions::buffer_size` bytes.
+    Buffered,
+    /// Memory maps all files, except empty files and e.g. pipes.
+    /// A file truncated while mapped, or an I/O error on e.g. a failing
+    /// or network drive, raises `SIGBUS` and aborts the process,
+    /// rather than returning an error for that file.
+    Mmap,
+}
+
+impl std::fmt::Display for IoStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IoStrategy::Auto => write!(f, "auto"),
+            IoStrategy::Buffered => write!(f, "buffered"),
+            IoStrategy::Mmap => write!(f, "mmap"),
+        }
+    }
+}
 
-/// Hash files. Optionally, limit how many bytes to hash via `len`.
-/// Returns hashmap with key: `<RELATIVE_PATH>`, value: `(<FULL_PATH, HASH>)`.
+impl std::str::FromStr for IoStrategy {
+    type Err = String;
+
+    /// Parses `auto`, `buffered`, or `mmap`. Case insensitive.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "auto" => Ok(IoStrategy::Auto),
+            "buffered" => Ok(IoStrategy::Buffered),
+            "mmap" => Ok(IoStrategy::Mmap),
+            _ => Err(format!("Invalid I/O strategy '{s}', expected auto, buffered, or mmap")),
+        }
+    }
+}
+
+/// Options for reading and hashing individual files.
+#[derive(Debug, Clone)]
+pub struct HashOptions {
+    /// Files of at least this size are hashed in several threads, one buffer
+    /// (or mapping for `IoStrategy::Mmap`) at a time. BLAKE3 and chunked hash types use all cores
+    /// for a single file, other hash types one thread each.
+    /// Digests are the same as for regular reads. `None` disables parallel hashing.
+    pub parallel_above: Option<u64>,
+    pub io: IoStrategy,
+    /// Read buffer size in bytes for `IoStrategy::Buffered`.
+    pub buffer_size: usize,
+    /// Hint that files are read sequentially, see `files::advise_sequential`. Linux only.
+    pub fadvise: bool,
+    /// Only hash this many bytes from the start of each file,
+    /// e.g. for a quick duplicates check.
+    pub partial: Option<usize>,
+}
+
+impl Default for HashOptions {
+    fn default() -> Self {
+        Self {
+            parallel_above: Some(PARALLEL_HASH_ABOVE),
+            io: IoStrategy::default(),
+            buffer_size: BUFFER_SIZE,
+            fadvise: false,
+            partial: None,
+        }
+    }
+}
+
+impl HashOptions {
+    /// `buffer_size` rounded up to a multiple of `PAGE_SIZE`, as used for reads.
+    pub fn aligned_buffer_size(&self) -> usize {
+        aligned_size(self.buffer_size)
+    }
+}
+
+/// Rounds `size` up to a multiple of `PAGE_SIZE`, at least `PAGE_SIZE`.
+fn aligned_size(size: usize) -> usize {
+    size.max(1).next_multiple_of(PAGE_SIZE)
+}
+
+/// Hash files. Each file is read once regardless of the number of hash types.
+/// Large files are hashed in several threads, and only part of each file
+/// if `hash_options.partial` is set, see `HashOptions`.
+/// Returns hashmap with key: `<RELATIVE_PATH>`, value: `(<FULL_PATH, [HASH, ...]>)`,
+/// with one hash per hash type in `hashtypes` in the same order.
+///
+/// See `HashContext` for caching, skipping files that fail to hash, and progress.
 pub fn hash_files(
     paths: &[PathBuf],
-    dirtype: &str,
-    hashtype: &HashType,
-    verbose: bool,
-    len: Option<usize>,
+    hashtypes: &[HashType],
+    hash_options: &HashOptions,
     strip_prefix: Option<&Path>,
-) -> Result<HashMap<PathBuf, (PathBuf, String)>, ChksumError> {
-    Ok(paths
-        .par_iter() // async better? compare ssd vs spinning disks
-        .map(|full_path| {
-            if !full_path.exists() {
-                return Err(ChksumError::FileDoesNotExist(full_path.to_owned()));
-                // or just continue?
-            }
+    context: HashContext,
+) -> Result<Hashes, ChksumError> {
+    hash_stream(paths.iter().cloned().map(Ok), hashtypes, hash_options, strip_prefix, context)
+}
 
-            let timestamp_in = OffsetDateTime::now_utc();
-            let (hash, size) = hash_file(full_path, hashtype, len)?;
-            let timestamp_out = OffsetDateTime::now_utc();
-
-            // Convert hash in bytes to hex string
-            let hex_string = hash
-                .iter()
-                .map(|b| format!("{:02x}", b))
-                .collect::<Vec<_>>()
-                .join("");
-
-            // forgot to pad hex string with zeros earlier so crappy len check
-            assert_eq!(hex_string.len(), hashtype.len(), "File hash has unexpected length");
-
-            if verbose {
-                // if print is not a single statement its print order
-                // is not consistent (expected rayon behaviour or bug? since this is inside a closure)
-                println!(
-                    "─ {} ┬ FILE {:23} {}\n         └ HASH {:23} {} {:12} bytes",
-                    dirtype,
-                    datetime_to_string(&timestamp_in),
-                    full_path.display(),
-                    datetime_to_string(&timestamp_out),
-                    hex_string,
-                    size
-                );
-            }
+/// Walks `dir` and hashes files at the same time: paths are passed
+/// from a walker thread to the hashing threads as they are found,
+/// see `PIPELINE_CAPACITY`. The walker runs in several threads if
+/// `walk_options.parallel` is set, see `walk::walk`. Walk errors are handled as for files
+/// that fail to hash, see `HashContext`, unless `walk_options.ignore_errors` is set, in which case
+/// they are skipped silently. The cache file is never hashed.
+///
+/// Returns hashes keyed on paths relative to `dir`. See `hash_files` for other arguments.
+pub fn hash_dir(
+    dir: &Path,
+    walk_options: &WalkOptions,
+    hashtypes: &[HashType],
+    hash_options: &HashOptions,
+    context: HashContext,
+) -> Result<Hashes, ChksumError> {
+    let cache_path = context.cache.as_ref().map(|cache| cache.path().to_owned());
 
-            let stripped_path = if let Some(prefix) = strip_prefix {
-                full_path.strip_prefix(prefix)?.to_owned()
-            } else {
-                full_path.to_owned()
+    std::thread::scope(|scope| {
+        let (sender, receiver) = sync_channel(PIPELINE_CAPACITY);
+        // receiver is dropped if hashing fails, which stops the walk
+        let walker = scope.spawn(move || walk(dir, walk_options, |path| sender.send(path).is_ok()));
+
+        let paths = receiver
+            .into_iter()
+            .filter(|path| path.as_ref().map_or(true, |p| Some(p) != cache_path.as_ref()))
+            .map(|path| path.map_err(ChksumError::from));
+        let hashes = hash_stream(paths, hashtypes, hash_options, Some(dir), context);
+
+        // e.g. invalid include/exclude patterns
+        walker.join().expect("Failed to join walker thread")?;
+        hashes
+    })
+}
+
+/// Same as `hash_files`, but hashes paths as they are yielded by `paths`,
+/// e.g. while a directory is still being walked. Paths are hashed in parallel,
+/// in no particular order. An `Err` in `paths`, e.g. a walk error, is handled
+/// as for a file that fails to hash.
+pub fn hash_stream<I>(
+    paths: I,
+    hashtypes: &[HashType],
+    hash_options: &HashOptions,
+    strip_prefix: Option<&Path>,
+    context: HashContext,
+) -> Result<Hashes, ChksumError>
+where
+    I: Iterator<Item = Result<PathBuf, ChksumError>> + Send,
+{
+    let HashContext { cache, errors, on_hashed } = context;
+
+    let strip = |full_path: &Path| -> Result<PathBuf, ChksumError> {
+        match strip_prefix {
+            Some(prefix) => Ok(full_path.strip_prefix(prefix)?.to_owned()),
+            None => Ok(full_path.to_owned()),
+        }
+    };
+
+    // Hashing threads only lock the cache for look ups,
+    // new hashes are inserted once all files are done
+    let cache = cache.filter(|_| hash_options.partial.is_none()).map(Mutex::new);
+
+    // Returns cached hashes if file metadata is unchanged.
+    // Metadata is read before hashing so that files modified while
+    // being hashed are re-hashed on the next run.
+    let hash_cached = |full_path: &Path, meta: &Option<FileMeta>| {
+        let cache = cache.as_ref()?;
+        let hex_strings = cache
+            .lock()
+            .expect("Failed to lock cache")
+            .get(full_path, meta.as_ref(), hashtypes)?;
+        if let Some(on_hashed) = on_hashed {
+            on_hashed(&HashEvent {
+                path: full_path,
+                size: meta.as_ref().map(|m| m.size),
+                hashes: Some(&hex_strings),
+                duration: Duration::ZERO,
+                cached: true,
+                error: None,
+            });
+        }
+        Some(hex_strings)
+    };
+
+    let hash_uncached = |full_path: &PathBuf, meta: &Option<FileMeta>| {
+        if !full_path.exists() {
+            return Err(ChksumError::FileDoesNotExist(full_path.to_path_buf()));
+        }
+
+        let (hashes, size) = hash_file(full_path, hashtypes, hash_options)?;
+
+        // Convert hashes in bytes to hex strings
+        let hex_strings = hashes
+            .iter()
+            .zip(hashtypes.iter())
+            .map(|(hash, hashtype)| {
+                let hex_string = hash
+                    .iter()
+                    .map(|b| format!("{:02x}", b))
+                    .collect::<Vec<_>>()
+                    .join("");
+
+                // forgot to pad hex string with zeros earlier so crappy len check
+                assert_eq!(hex_string.len(), hashtype.len(), "File hash has unexpected length");
+
+                hex_string
+            })
+            .collect::<Vec<_>>();
+
+        // not using hash as key since duplicate file hashes will be discarded
+        // whereas relative path will be unique
+        Ok((strip(full_path)?, (full_path.to_path_buf(), hex_strings), meta.to_owned(), size))
+    };
+
+    // Returns metadata for files to insert in the cache, `None` for cached hashes
+    let hash_one = |full_path: PathBuf| {
+        let meta = cache.as_ref().and_then(|_| FileMeta::from_path(&full_path));
+        if let Some(hex_strings) = hash_cached(&full_path, &meta) {
+            return Ok((strip(&full_path)?, (full_path, hex_strings), None));
+        }
+
+        let started = Instant::now();
+        let result = hash_uncached(&full_path, &meta);
+        if let Some(on_hashed) = on_hashed {
+            let (size, hashes, error) = match &result {
+                Ok((_, (_, hex_strings), _, size)) => (Some(*size), Some(hex_strings.as_slice()), None),
+                Err(err) => (None, None, Some(err)),
             };
+            on_hashed(&HashEvent {
+                path: &full_path,
+                size,
+                hashes,
+                duration: started.elapsed(),
+                cached: false,
+                error,
+            });
+        }
+        result.map(|(stripped_path, hashes, meta, _size)| (stripped_path, hashes, meta))
+    };
 
-            // not using hash as key since duplicate file hashes will be discarded
-            // whereas relative path will be unique
-            Ok((stripped_path, (full_path.to_owned(), hex_string)))
-        })
-        .collect::<Result<HashMap<PathBuf, (PathBuf, String)>, ChksumError>>()?)
+    // async better? compare ssd vs spinning disks
+    let hashed = match errors {
+        Some(errors) => {
+            let results = paths
+                .par_bridge()
+                .map(|path| path.and_then(hash_one))
+                .collect::<Vec<Result<_, ChksumError>>>();
+            let mut hashed = Vec::with_capacity(results.len());
+            for result in results {
+                match result {
+                    Ok(h) => hashed.push(h),
+                    Err(err) => errors.push(err),
+                }
+            }
+            hashed
+        }
+        None => paths
+            .par_bridge()
+            .map(|path| path.and_then(hash_one))
+            .collect::<Result<Vec<_>, ChksumError>>()?,
+    };
+
+    let mut cache = cache.map(|cache| cache.into_inner().expect("Failed to lock cache"));
+    let mut hashes: Hashes = HashMap::with_capacity(hashed.len());
+    for (stripped_path, (full_path, hex_strings), meta) in hashed {
+        if let (Some(cache), Some(meta)) = (cache.as_deref_mut(), meta) {
+            cache.insert(&full_path, meta, hashtypes, &hex_strings);
+        }
+        hashes.insert(stripped_path, (full_path, hex_strings));
+    }
+
+    Ok(hashes)
 }
 
-/// Organise hashes (`<RELATIVE_PATH>`, value: `(<FULL_PATH, HASH>)`)
+/// Organise hashes (`<RELATIVE_PATH>`, value: `(<FULL_PATH, [HASH, ...]>)`)
 /// as `<KEY: hash, VAL: [sorted paths]>` to group duplicate files.
+/// For multiple hash types the key is the space separated hashes.
 ///
 /// `prune_unique = true` prunes all values (`Vec<PathBuf>`) with length 1.
 pub fn hash2path(
-    hashes: &HashMap<PathBuf, (PathBuf, String)>,
+    hashes: &Hashes,
     prune_unique: bool
 ) -> HashMap<String, Vec<PathBuf>> {
     let mut hash2paths: HashMap<String, Vec<PathBuf>> = HashMap::new();
     hashes.iter().for_each(|(_path, (full_path, hash))| {
-        let entry = hash2paths.entry(hash.to_owned());
+        let entry = hash2paths.entry(hash.join(" "));
         entry.or_default().push(full_path.to_owned())
     });
 
@@ -95,67 +427,573 @@ pub fn hash2path(
     hash2paths
 }
 
-#[derive(Debug, Clone)]
+/// Pairs paths only in source (`missing`) with paths only in target (`ignored`)
+/// that have identical hashes, i.e. files that have been moved or renamed.
+/// Returns `(<SOURCE_RELATIVE_PATH>, <TARGET_RELATIVE_PATH>)` sorted on source path.
+///
+/// If several files share a hash, paths are paired in sorted order.
+pub fn match_moved(
+    missing: &HashSet<PathBuf>,
+    ignored: &HashSet<PathBuf>,
+    source_hashes: &Hashes,
+    target_hashes: &Hashes,
+) -> Vec<(PathBuf, PathBuf)> {
+    let mut hash2ignored: HashMap<&[String], VecDeque<&PathBuf>> = HashMap::new();
+    let mut ignored_sorted: Vec<&PathBuf> = ignored.iter().collect();
+    ignored_sorted.sort();
+    for path in ignored_sorted {
+        if let Some((_, hash)) = target_hashes.get(path) {
+            hash2ignored.entry(hash.as_slice()).or_default().push_back(path);
+        }
+    }
+
+    let mut missing_sorted: Vec<&PathBuf> = missing.iter().collect();
+    missing_sorted.sort();
+
+    missing_sorted
+        .into_iter()
+        .filter_map(|path| {
+            let (_, hash) = source_hashes.get(path)?;
+            let target_path = hash2ignored.get_mut(hash.as_slice())?.pop_front()?;
+            Some((path.to_owned(), target_path.to_owned()))
+        })
+        .collect()
+}
+
+/// Returns log headers, one per hash type, e.g.
+/// `["SOURCEMD5", "SOURCESHA256"]` for prefix `SOURCE`.
+pub fn hash_headers(hashtypes: &[HashType], prefix: &str) -> Vec<String> {
+    hashtypes
+        .iter()
+        .map(|h| format!("{prefix}{h}"))
+        .collect()
+}
+
+/// Returns comma separated hash type names, e.g. `MD5,SHA256`.
+pub fn hash_types_to_string(hashtypes: &[HashType]) -> String {
+    hashtypes
+        .iter()
+        .map(|h| h.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum HashType {
     Sha256,
     Blake3,
     Md5,
+    Sha512,
+    Sha1,
+    Sha3_256,
+    /// BLAKE2b with 512-bit output, as `b2sum`.
+    Blake2b,
+    /// Non-cryptographic. 64-bit XXH3.
+    Xxh3_64,
+    /// Non-cryptographic. 128-bit XXH3 (XXH128).
+    Xxh3_128,
+    /// Non-cryptographic. CRC-32C (Castagnoli).
+    Crc32c,
+    /// Files are split into chunks of this many bytes (the last may be shorter),
+    /// each chunk is hashed, and the chunk digests are hashed in order with the same
+    /// hash type. Chunks can be hashed in parallel for any hash type, but the digest
+    /// differs from that of the whole file, e.g. `sha256sum` output.
+    /// Labelled `<HASHTYPE>-CHUNKED-<BYTES>`, e.g. `SHA256-CHUNKED-1048576`.
+    Chunked(Box<HashType>, u64),
+}
+
+impl std::fmt::Display for HashType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HashType::Blake3 => write!(f, "BLAKE3"),
+            HashType::Sha256 => write!(f, "SHA256"),
+            HashType::Md5 => write!(f, "MD5"),
+            HashType::Sha512 => write!(f, "SHA512"),
+            HashType::Sha1 => write!(f, "SHA1"),
+            HashType::Sha3_256 => write!(f, "SHA3-256"),
+            HashType::Blake2b => write!(f, "BLAKE2B"),
+            HashType::Xxh3_64 => write!(f, "XXH3-64"),
+            HashType::Xxh3_128 => write!(f, "XXH3-128"),
+            HashType::Crc32c => write!(f, "CRC32C"),
+            HashType::Chunked(hashtype, chunk_size) => write!(f, "{hashtype}-CHUNKED-{chunk_size}"),
+        }
+    }
+}
+
+impl std::str::FromStr for HashType {
+    type Err = ChksumError;
+
+    /// Parses hash type from e.g. a log header. Case insensitive.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some((hashtype, chunk_size)) = s.trim().to_ascii_uppercase().rsplit_once("-CHUNKED-") {
+            return match (Self::from_str(hashtype), chunk_size.parse::<u64>()) {
+                (Ok(hashtype), Ok(chunk_size)) if chunk_size > 0 && hashtype.chunk_size().is_none() => {
+                    Ok(hashtype.chunked(chunk_size))
+                }
+                _ => Err(ChksumError::UnknownHashType(s.to_owned())),
+            };
+        }
+        match s.trim().to_ascii_uppercase().replace(['-', '_'], "").as_str() {
+            "BLAKE3" => Ok(HashType::Blake3),
+            "SHA256" => Ok(HashType::Sha256),
+            "MD5" => Ok(HashType::Md5),
+            "SHA512" => Ok(HashType::Sha512),
+            "SHA1" => Ok(HashType::Sha1),
+            "SHA3256" => Ok(HashType::Sha3_256),
+            "BLAKE2B" | "BLAKE2B512" => Ok(HashType::Blake2b),
+            "XXH3" | "XXH364" => Ok(HashType::Xxh3_64),
+            "XXH128" | "XXH3128" => Ok(HashType::Xxh3_128),
+            "CRC32C" => Ok(HashType::Crc32c),
+            _ => Err(ChksumError::UnknownHashType(s.to_owned())),
+        }
+    }
 }
 
 impl HashType {
-    pub fn to_string(&self) -> String {
-        match &self {
-            HashType::Blake3 => "BLAKE3".to_owned(),
-            HashType::Sha256 => "SHA256".to_owned(),
-            HashType::Md5 => "MD5".to_owned(),
+    /// Conventional filename for a GNU coreutils style manifest,
+    /// e.g. `SHA256SUMS`.
+    pub fn sums_filename(&self) -> String {
+        match self {
+            HashType::Sha256 => "SHA256SUMS".to_owned(),
+            HashType::Blake3 => "B3SUMS".to_owned(),
+            HashType::Md5 => "MD5SUMS".to_owned(),
+            HashType::Sha512 => "SHA512SUMS".to_owned(),
+            HashType::Sha1 => "SHA1SUMS".to_owned(),
+            HashType::Sha3_256 => "SHA3-256SUMS".to_owned(),
+            HashType::Blake2b => "B2SUMS".to_owned(),
+            HashType::Xxh3_64 => "XXH3SUMS".to_owned(),
+            HashType::Xxh3_128 => "XXH128SUMS".to_owned(),
+            HashType::Crc32c => "CRC32CSUMS".to_owned(),
+            HashType::Chunked(..) => format!("{self}SUMS"),
+        }
+    }
+
+    /// Infers hash type from a GNU coreutils style manifest filename,
+    /// e.g. `SHA256SUMS`, `files.md5`, or `B3SUMS`.
+    pub fn from_sums_filename(path: &Path) -> Option<Self> {
+        let filename = path.file_name()?.to_string_lossy().to_ascii_lowercase();
+        let ext = path.extension().map(|e| e.to_string_lossy().to_ascii_lowercase());
+        if filename.contains("sha3") {
+            Some(HashType::Sha3_256)
+        } else if filename.contains("sha256") {
+            Some(HashType::Sha256)
+        } else if filename.contains("sha512") {
+            Some(HashType::Sha512)
+        } else if filename.contains("sha1") {
+            Some(HashType::Sha1)
+        } else if filename.contains("md5") {
+            Some(HashType::Md5)
+        } else if filename.contains("blake3") || filename.starts_with("b3sum") || ext.as_deref() == Some("b3") {
+            Some(HashType::Blake3)
+        } else if filename.contains("blake2") || filename.starts_with("b2sum") || ext.as_deref() == Some("b2") {
+            Some(HashType::Blake2b)
+        } else if filename.contains("xxh128") || filename.contains("xxh3-128") || filename.contains("xxh3_128") {
+            Some(HashType::Xxh3_128)
+        } else if filename.contains("xxh3") {
+            Some(HashType::Xxh3_64)
+        } else if filename.contains("crc32c") {
+            Some(HashType::Crc32c)
+        } else {
+            None
         }
     }
 
+    /// Length of the hex string for this hash type.
+    #[allow(clippy::len_without_is_empty)]
     pub fn len(&self) -> usize {
         match self {
             HashType::Sha256 => 64,
             HashType::Blake3 => 64,
             HashType::Md5 => 32,
+            HashType::Sha512 => 128,
+            HashType::Sha1 => 40,
+            HashType::Sha3_256 => 64,
+            HashType::Blake2b => 128,
+            HashType::Xxh3_64 => 16,
+            HashType::Xxh3_128 => 32,
+            HashType::Crc32c => 8,
+            HashType::Chunked(hashtype, _) => hashtype.len(),
+        }
+    }
+
+    /// Returns `false` for hash types without collision resistance,
+    /// only suitable for detecting accidental corruption.
+    pub fn is_cryptographic(&self) -> bool {
+        match self {
+            HashType::Chunked(hashtype, _) => hashtype.is_cryptographic(),
+            _ => !matches!(self, HashType::Xxh3_64 | HashType::Xxh3_128 | HashType::Crc32c),
+        }
+    }
+
+    /// Returns the chunked variant of this hash type, see `HashType::Chunked`.
+    /// Chunked hash types are returned as is.
+    pub fn chunked(self, chunk_size: u64) -> Self {
+        match self {
+            HashType::Chunked(..) => self,
+            _ => HashType::Chunked(Box::new(self), chunk_size),
+        }
+    }
+
+    /// Chunk size in bytes for chunked hash types.
+    pub fn chunk_size(&self) -> Option<u64> {
+        match self {
+            HashType::Chunked(_, chunk_size) => Some(*chunk_size),
+            _ => None,
+        }
+    }
+}
+
+/// Hashing state for a single hash type.
+enum Hasher {
+    Sha256(Sha256),
+    Blake3(Box<blake3::Hasher>),
+    Md5(Md5),
+    Sha512(Sha512),
+    Sha1(Sha1),
+    Sha3_256(Sha3_256),
+    Blake2b(Blake2b512),
+    Xxh3_64(Box<Xxh3>),
+    Xxh3_128(Box<Xxh3>),
+    Crc32c(u32),
+    Chunked(Box<ChunkedHasher>),
+}
+
+impl Hasher {
+    fn new(hashtype: &HashType) -> Self {
+        match hashtype {
+            HashType::Sha256 => Hasher::Sha256(Sha256::new()),
+            HashType::Blake3 => Hasher::Blake3(Box::new(blake3::Hasher::new())),
+            HashType::Md5 => Hasher::Md5(Md5::new()),
+            HashType::Sha512 => Hasher::Sha512(Sha512::new()),
+            HashType::Sha1 => Hasher::Sha1(Sha1::new()),
+            HashType::Sha3_256 => Hasher::Sha3_256(Sha3_256::new()),
+            HashType::Blake2b => Hasher::Blake2b(Blake2b512::new()),
+            HashType::Xxh3_64 => Hasher::Xxh3_64(Box::new(Xxh3::new())),
+            HashType::Xxh3_128 => Hasher::Xxh3_128(Box::new(Xxh3::new())),
+            HashType::Crc32c => Hasher::Crc32c(0),
+            HashType::Chunked(hashtype, chunk_size) => Hasher::Chunked(Box::new(ChunkedHasher {
+                hashtype: hashtype.as_ref().to_owned(),
+                chunk_size: *chunk_size,
+                chunk: Hasher::new(hashtype),
+                chunk_len: 0,
+                pending: None,
+                digests: Hasher::new(hashtype),
+            })),
+        }
+    }
+
+    fn update(&mut self, buf: &[u8]) {
+        match self {
+            Hasher::Sha256(h) => h.update(buf),
+            Hasher::Blake3(h) => {
+                h.update(buf);
+            }
+            Hasher::Md5(h) => h.update(buf),
+            Hasher::Sha512(h) => h.update(buf),
+            Hasher::Sha1(h) => h.update(buf),
+            Hasher::Sha3_256(h) => h.update(buf),
+            Hasher::Blake2b(h) => h.update(buf),
+            Hasher::Xxh3_64(h) | Hasher::Xxh3_128(h) => h.update(buf),
+            Hasher::Crc32c(crc) => *crc = crc32c::crc32c_append(*crc, buf),
+            Hasher::Chunked(h) => h.update(buf),
+        }
+    }
+
+    /// Same as `update`, but BLAKE3 hashes `buf` in several threads.
+    fn update_rayon(&mut self, buf: &[u8]) {
+        match self {
+            Hasher::Blake3(h) => {
+                h.update_rayon(buf);
+            }
+            Hasher::Chunked(h) => h.update_rayon(buf),
+            h => h.update(buf),
         }
     }
+
+    fn finalize(self) -> Vec<u8> {
+        match self {
+            Hasher::Sha256(h) => h.finalize().to_vec(),
+            Hasher::Blake3(h) => h.finalize().as_bytes().to_vec(),
+            Hasher::Md5(h) => h.finalize().to_vec(),
+            Hasher::Sha512(h) => h.finalize().to_vec(),
+            Hasher::Sha1(h) => h.finalize().to_vec(),
+            Hasher::Sha3_256(h) => h.finalize().to_vec(),
+            Hasher::Blake2b(h) => h.finalize().to_vec(),
+            // canonical (big-endian) representation, as xxhsum
+            Hasher::Xxh3_64(h) => h.digest().to_be_bytes().to_vec(),
+            Hasher::Xxh3_128(h) => h.digest128().to_be_bytes().to_vec(),
+            Hasher::Crc32c(crc) => crc.to_be_bytes().to_vec(),
+            Hasher::Chunked(h) => h.finalize(),
+        }
+    }
+}
+
+/// Upper limit in bytes for chunks buffered by `ChunkedHasher::update_rayon`,
+/// shared by all files being hashed.
+const CHUNK_BUFFER_LIMIT: u64 = 256_000_000;
+
+/// Bytes currently reserved by all `ChunkBuffer`s.
+static CHUNK_BUFFER_RESERVED: AtomicU64 = AtomicU64::new(0);
+
+/// Input buffered by `ChunkedHasher::update_rayon`, starting at a chunk boundary.
+/// Released to `CHUNK_BUFFER_LIMIT` when dropped.
+struct ChunkBuffer {
+    data: Vec<u8>,
+    reserved: u64,
 }
 
-/// Calculates hash, and returns `(hash_as_bytes, bytes_read)`.
+impl ChunkBuffer {
+    /// Returns an empty buffer for `capacity` bytes, or `None` if buffers
+    /// for other files already take up `CHUNK_BUFFER_LIMIT`.
+    fn reserve(capacity: usize) -> Option<Self> {
+        let reserved = capacity as u64;
+        CHUNK_BUFFER_RESERVED
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |r| {
+                (r + reserved <= CHUNK_BUFFER_LIMIT).then_some(r + reserved)
+            })
+            .ok()?;
+        Some(Self { data: Vec::with_capacity(capacity), reserved })
+    }
+}
+
+impl Drop for ChunkBuffer {
+    fn drop(&mut self) {
+        CHUNK_BUFFER_RESERVED.fetch_sub(self.reserved, Ordering::SeqCst);
+    }
+}
+
+/// Hashing state for `HashType::Chunked`.
+struct ChunkedHasher {
+    hashtype: HashType,
+    chunk_size: u64,
+    /// Current chunk.
+    chunk: Hasher,
+    /// Bytes in current chunk.
+    chunk_len: u64,
+    /// Input buffered by `update_rayon` until it holds enough
+    /// complete chunks to hash them in parallel.
+    pending: Option<ChunkBuffer>,
+    /// Digests of all completed chunks.
+    digests: Hasher,
+}
+
+impl ChunkedHasher {
+    fn update(&mut self, buf: &[u8]) {
+        self.stream(buf, false);
+    }
+
+    /// Hashes `buf` into the current chunk, one chunk at a time.
+    /// If `parallel` is set, each chunk is hashed as for `Hasher::update_rayon`.
+    fn stream(&mut self, mut buf: &[u8], parallel: bool) {
+        self.flush_pending();
+        while !buf.is_empty() {
+            let n = buf.len().min((self.chunk_size - self.chunk_len) as usize);
+            match parallel {
+                true => self.chunk.update_rayon(&buf[..n]),
+                false => self.chunk.update(&buf[..n]),
+            }
+            self.chunk_len += n as u64;
+            buf = &buf[n..];
+            if self.chunk_len == self.chunk_size {
+                let chunk = std::mem::replace(&mut self.chunk, Hasher::new(&self.hashtype));
+                self.digests.update(&chunk.finalize());
+                self.chunk_len = 0;
+            }
+        }
+    }
+
+    /// Same as `update`, but complete chunks are hashed in parallel.
+    /// Input that does not fill a batch of one chunk per thread is buffered
+    /// and hashed with later input. Chunks are hashed one at a time if they are larger
+    /// than `CHUNK_BUFFER_LIMIT`, or if buffers for other files take up the limit.
+    fn update_rayon(&mut self, mut buf: &[u8]) {
+        let chunks = (CHUNK_BUFFER_LIMIT / self.chunk_size).min(rayon::current_num_threads() as u64);
+        if chunks < 2 {
+            return self.stream(buf, true);
+        }
+        let batch = (chunks * self.chunk_size) as usize;
+
+        // complete a chunk started by `update`, so that the rest starts at a chunk boundary
+        if self.chunk_len > 0 {
+            let n = buf.len().min((self.chunk_size - self.chunk_len) as usize);
+            self.stream(&buf[..n], true);
+            buf = &buf[n..];
+        }
+
+        while !buf.is_empty() {
+            if self.pending.as_ref().is_none_or(|p| p.data.is_empty()) && buf.len() >= batch {
+                // hash directly, e.g. for memory mapped files
+                let split = buf.len() - buf.len() % self.chunk_size as usize;
+                self.hash_chunks(&buf[..split]);
+                buf = &buf[split..];
+                continue;
+            }
+            let Some(mut pending) = self.pending.take().or_else(|| ChunkBuffer::reserve(batch)) else {
+                return self.stream(buf, true);
+            };
+            let n = buf.len().min(batch - pending.data.len());
+            pending.data.extend_from_slice(&buf[..n]);
+            buf = &buf[n..];
+            if pending.data.len() == batch {
+                self.hash_chunks(&pending.data);
+                pending.data.clear();
+            }
+            self.pending = Some(pending);
+        }
+    }
+
+    /// Hashes complete chunks in `buf` in parallel.
+    fn hash_chunks(&mut self, buf: &[u8]) {
+        let digests = buf
+            .par_chunks(self.chunk_size as usize)
+            .map(|chunk| {
+                let mut hasher = Hasher::new(&self.hashtype);
+                hasher.update_rayon(chunk);
+                hasher.finalize()
+            })
+            .collect::<Vec<_>>();
+        digests.iter().for_each(|digest| self.digests.update(digest));
+    }
+
+    /// Hashes buffered input, complete chunks in parallel.
+    /// A trailing partial chunk becomes the current chunk.
+    fn flush_pending(&mut self) {
+        let Some(pending) = self.pending.take() else {
+            return;
+        };
+        let split = pending.data.len() - pending.data.len() % self.chunk_size as usize;
+        self.hash_chunks(&pending.data[..split]);
+        self.chunk.update(&pending.data[split..]);
+        self.chunk_len = (pending.data.len() - split) as u64;
+    }
+
+    /// Empty input has no chunks, i.e. the digest is the hash of empty input.
+    fn finalize(mut self) -> Vec<u8> {
+        self.flush_pending();
+        if self.chunk_len > 0 {
+            self.digests.update(&self.chunk.finalize());
+        }
+        self.digests.finalize()
+    }
+}
+
+/// Feeds a single stream into several hashers,
+/// so that multiple hash types only require a single read.
+struct MultiHasher(Vec<Hasher>);
+
+impl MultiHasher {
+    fn new(hashtypes: &[HashType]) -> Self {
+        Self(hashtypes.iter().map(Hasher::new).collect())
+    }
+
+    /// Same as `write`, but each hash type is hashed in its own thread,
+    /// see `Hasher::update_rayon`.
+    fn update_rayon(&mut self, buf: &[u8]) {
+        self.0.par_iter_mut().for_each(|h| h.update_rayon(buf));
+    }
+
+    fn finalize(self) -> Vec<Vec<u8>> {
+        self.0.into_iter().map(|h| h.finalize()).collect()
+    }
+}
+
+impl Write for MultiHasher {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.iter_mut().for_each(|h| h.update(buf));
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Calculates hashes, one per hash type in `hashtypes`, in a single read
+/// and returns `([hash_as_bytes, ...], bytes_read)`.
 pub fn hash_reader<R: Read>(
     reader: &mut R,
-    hashtype: &HashType,
-) -> std::io::Result<(Vec<u8>, u64)> {
-    let hash: Vec<u8>;
-    let size: u64;
+    hashtypes: &[HashType],
+) -> std::io::Result<(Vec<Vec<u8>>, u64)> {
+    let mut hasher = MultiHasher::new(hashtypes);
+    let size = copy(reader, &mut hasher)?;
+
+    Ok((hasher.finalize(), size))
+}
+
+/// Same as `hash_reader`, but reads into a page aligned buffer of `buffer_size` bytes,
+/// rounded up to a multiple of `PAGE_SIZE`. If `parallel` is set, each buffer is hashed
+/// in several threads as for `hash_mmap`. Digests are the same.
+pub fn hash_buffered<R: Read>(
+    reader: &mut R,
+    hashtypes: &[HashType],
+    buffer_size: usize,
+    parallel: bool,
+) -> std::io::Result<(Vec<Vec<u8>>, u64)> {
+    let buffer_size = aligned_size(buffer_size);
+    let mut storage = vec![0_u8; buffer_size + PAGE_SIZE];
+    // unaligned in the unlikely case that the offset can not be computed
+    let offset = storage.as_ptr().align_offset(PAGE_SIZE).min(PAGE_SIZE);
+    let buf = &mut storage[offset..offset + buffer_size];
 
-    match hashtype {
-        &HashType::Blake3 => {
-            let mut hasher = blake3::Hasher::new();
-            size = copy(reader, &mut hasher)?;
-            hash = hasher.finalize().as_bytes().to_ascii_lowercase()
+    let mut hasher = MultiHasher::new(hashtypes);
+    let mut size = 0;
+    loop {
+        // fill the buffer, since reads may return less than requested
+        let mut len = 0;
+        while len < buf.len() {
+            match reader.read(&mut buf[len..]) {
+                Ok(0) => break,
+                Ok(n) => len += n,
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            }
         }
-        &HashType::Sha256 => {
-            let mut hasher = Sha256::new();
-            size = copy(reader, &mut hasher)?;
-            hash = hasher.finalize().to_ascii_lowercase();
+        if len == 0 {
+            break;
         }
-        &HashType::Md5 => {
-            let mut hasher = Md5::new();
-            size = copy(reader, &mut hasher)?;
-            hash = hasher.finalize().to_ascii_lowercase();
+        match parallel {
+            true => hasher.update_rayon(&buf[..len]),
+            false => hasher.write_all(&buf[..len])?,
         }
+        size += len as u64;
+    }
+
+    Ok((hasher.finalize(), size))
+}
+
+/// Same as `hash_reader`, but for a memory mapped file. If `parallel` is set,
+/// BLAKE3 and chunked hash types use all cores, and each other hash type
+/// is hashed in its own thread. Digests are the same.
+///
+/// Truncating the file while it is hashed, or an I/O error while reading it,
+/// raises `SIGBUS` and aborts the process. See `IoStrategy::Mmap`.
+pub fn hash_mmap(
+    file: &File,
+    hashtypes: &[HashType],
+    parallel: bool,
+) -> std::io::Result<(Vec<Vec<u8>>, u64)> {
+    // Safety: the mapping is read only. The file being modified while hashed
+    // gives an inconsistent hash, as for regular reads, truncating it may raise SIGBUS.
+    let mmap = unsafe { memmap2::Mmap::map(file)? };
+    let mut hasher = MultiHasher::new(hashtypes);
+    match parallel {
+        true => hasher.update_rayon(&mmap),
+        false => hasher.write_all(&mmap)?,
     }
 
-    Ok((hash, size))
+    Ok((hasher.finalize(), mmap.len() as u64))
 }
 
-/// Hashes file, and returns `(hash_as_bytes, bytes_read)`.
+/// Hashes file, and returns `([hash_as_bytes, ...], bytes_read)`,
+/// with one hash per hash type in `hashtypes`.
+/// Files are read as set by `hash_options.io`, see `IoStrategy`,
+/// and only in part if `hash_options.partial` is set.
 pub fn hash_file(
     path: &Path,
-    hashtype: &HashType,
-    len: Option<usize>,
-) -> Result<(Vec<u8>, u64), ChksumError> {
-    if let Some(l) = len {
+    hashtypes: &[HashType],
+    hash_options: &HashOptions,
+) -> Result<(Vec<Vec<u8>>, u64), ChksumError> {
+    if let Some(l) = hash_options.partial {
         let mut buf: Vec<u8> = vec![0; l];
         // Not important wether n == len here, i.e. file smaller than n bytes,
         // should not raise error.
@@ -167,54 +1005,133 @@ pub fn hash_file(
             .read(&mut buf)
             .map_err(|err| ChksumError::ReadFileFailed((path.to_owned(), err)))?;
 
-        hash_reader(&mut Cursor::new(&buf), hashtype)
+        hash_reader(&mut Cursor::new(&buf), hashtypes)
             .map_err(|err| ChksumError::PartialHashFailed((path.to_owned(), err)))
     } else {
         let mut file =
             File::open(path).map_err(|err| ChksumError::OpenFileFailed((path.to_owned(), err)))?;
-        hash_reader(&mut file, hashtype)
-            .map_err(|err| ChksumError::HashFailed((path.to_owned(), err)))
-        // hash_file_par(path, hashtype)
+        if hash_options.fadvise {
+            advise_sequential(&file);
+        }
+        // empty files and e.g. pipes can not be mapped
+        let mappable_size = file.metadata()
+            .ok()
+            .filter(|m| m.is_file() && m.len() > 0)
+            .map(|m| m.len());
+        let parallel = mappable_size
+            .zip(hash_options.parallel_above)
+            .is_some_and(|(size, threshold)| size >= threshold);
+        match hash_options.io {
+            IoStrategy::Mmap if mappable_size.is_some() => hash_mmap(&file, hashtypes, parallel),
+            IoStrategy::Buffered => hash_buffered(&mut file, hashtypes, hash_options.buffer_size, parallel),
+            _ if parallel => hash_buffered(&mut file, hashtypes, hash_options.buffer_size, true),
+            _ => hash_reader(&mut file, hashtypes),
+        }
+        .map_err(|err| ChksumError::HashFailed((path.to_owned(), err)))
     }
 }
 
-// seems slightly faster on spinning disk but much slower on ssd???
-pub fn hash_file_par(
-    path: &Path,
-    hashtype: &HashType,
-) -> Result<(Vec<u8>, u64), ChksumError> {
-    let mut file = File::open(&path).unwrap();
-    let file_len = file.metadata()?.len();
-    // let mut reader = BufReader::new(file);
-    let chunk_size = 1_000_000; // bytes
-    let chunk_num = num_cpus::get();
-    let mut progress = 0_u64;
-    let mut chunk_hashes: Vec<Vec<u8>> = Vec::new();
-    // while reader.stream_position().unwrap() < file_len {
-    while progress < file_len {
-        let mut chunks = (0..chunk_num * 2).into_iter()
-            .filter_map(|_| {
-                    let mut buf = vec![0; chunk_size];
-                    // let mut buf = [0; 1_000_000];
-                    match file.read(&mut buf) {
-                        Ok(n) => {
-                            progress += n as u64;
-                            Some(Cursor::new(buf))
-                        },
-                        Err(_) => None,
-                    }
-                    // reader.read(&mut buf).unwrap();
-                })
-            .collect::<Vec<_>>();
-        let hashes = chunks.par_iter_mut()
-            .map(|c| {
-                let (hash, _) = hash_reader(c, hashtype).unwrap();
-                hash
-            })
-            .collect::<Vec<_>>();
-        chunk_hashes.extend(hashes);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// SHA-256 of the SHA-256 digests of each `chunk_size` chunk of `data`.
+    fn chunked_sha256(data: &[u8], chunk_size: usize) -> Vec<u8> {
+        let mut digests = Sha256::new();
+        data.chunks(chunk_size).for_each(|chunk| digests.update(Sha256::digest(chunk)));
+        digests.finalize().to_vec()
     }
 
-    hash_reader(&mut Cursor::new(chunk_hashes.into_iter().flatten().collect::<Vec<_>>()), hashtype)
-        .map_err(|e| e.into())
+    fn data(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 31 % 251) as u8).collect()
+    }
+
+    #[test]
+    fn chunked_digest_layout() {
+        let hashtype = HashType::Sha256.chunked(1000);
+        for len in [0, 1, 999, 1000, 1001, 10_500] {
+            let data = data(len);
+            let (hashes, size) = hash_reader(&mut Cursor::new(&data), std::slice::from_ref(&hashtype)).unwrap();
+            assert_eq!(size, len as u64);
+            assert_eq!(hashes[0], chunked_sha256(&data, 1000), "{len} bytes");
+        }
+    }
+
+    #[test]
+    fn chunked_update_rayon_matches_update() {
+        let hashtype = HashType::Sha256.chunked(1000);
+        let data = data(25_300);
+        let expected = chunked_sha256(&data, 1000);
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(4).build().unwrap();
+
+        pool.install(|| {
+            // buffers smaller than, larger than, and not aligned to chunks and batches
+            for buf_len in [1, 300, 1000, 1700, 4000, 9999, data.len()] {
+                let mut hasher = Hasher::new(&hashtype);
+                data.chunks(buf_len).for_each(|buf| hasher.update_rayon(buf));
+                assert_eq!(hasher.finalize(), expected, "{buf_len} byte buffers");
+            }
+
+            // serial updates in between parallel ones
+            let mut hasher = Hasher::new(&hashtype);
+            for (i, buf) in data.chunks(1700).enumerate() {
+                match i % 3 {
+                    0 => hasher.update(buf),
+                    _ => hasher.update_rayon(buf),
+                }
+            }
+            assert_eq!(hasher.finalize(), expected);
+
+            // limit taken up by other files
+            let reserved = ChunkBuffer::reserve(CHUNK_BUFFER_LIMIT as usize).unwrap();
+            let mut hasher = Hasher::new(&hashtype);
+            data.chunks(1700).for_each(|buf| hasher.update_rayon(buf));
+            assert_eq!(hasher.finalize(), expected);
+            drop(reserved);
+        });
+        assert_eq!(CHUNK_BUFFER_RESERVED.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn legacy_digest_lowercases_bytes() {
+        // SHA-256 for "file 1\n", and as logged by chksum 0.5.0
+        let hash = "5f5d584c5857d85af911ade1b2ae7cb593c17654282091f3ace31efd9e951360";
+        let legacy = "5f5d786c7877d87af911ade1b2ae7cb593c17674282091f3ace31efd9e951360";
+        assert_eq!(legacy_digest(hash).as_deref(), Some(legacy));
+        assert_eq!(legacy_digest(legacy), None);
+        assert_eq!(legacy_digest("5a5"), None);
+        assert!(is_legacy_match(&[legacy.to_owned()], &[hash.to_owned()]));
+        assert!(!is_legacy_match(&[hash.to_owned()], &[hash.to_owned()]));
+        assert!(!is_legacy_match(&[hash.to_owned()], &[legacy.to_owned()]));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn hash_dir_unreadable_dir() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let root = std::env::temp_dir().join(format!("chksum_unreadable_test_{}", std::process::id()));
+        let locked = root.join("locked");
+        std::fs::create_dir_all(&locked).unwrap();
+        std::fs::write(root.join("a.txt"), "a").unwrap();
+        std::fs::write(locked.join("b.txt"), "b").unwrap();
+        std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o000)).unwrap();
+
+        // permissions do not apply to root
+        if std::fs::read_dir(&locked).is_err() {
+            for parallel in [false, true] {
+                let options = WalkOptions { parallel, ..WalkOptions::default() };
+                let hash = |errors| hash_dir(&root, &options, &[HashType::Blake3], &HashOptions::default(), HashContext { errors, ..HashContext::default() });
+                assert!(hash(None).is_err());
+
+                let mut errors = Vec::new();
+                let hashes = hash(Some(&mut errors)).unwrap();
+                assert_eq!(hashes.keys().collect::<Vec<_>>(), [Path::new("a.txt")]);
+                assert_eq!(errors.len(), 1);
+            }
+        }
+
+        std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o755)).unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+    }
 }
@@ -0,0 +1,36 @@
+//! Calculate checksums for all files in a directory recursively, compare directories
+//! or manifests, verify manifests, and find duplicate files.
+//!
+//! The `chksum` binary is a command line interface over this library.
+//!
+//! ```no_run
+//! use std::path::Path;
+//! use chksum::{compare::{compare_trees, CompareOptions, TreeContext, TreeSource}, hash::HashType};
+//!
+//! let options = CompareOptions { hash_types: vec![HashType::Blake3], ..CompareOptions::default() };
+//! // files are hashed while each directory is walked
+//! let comparison = compare_trees(
+//!     TreeSource::Dir(Path::new("source")),
+//!     Some(TreeSource::Dir(Path::new("target"))),
+//!     &options,
+//!     TreeContext::default(),
+//!     TreeContext::default(),
+//! )?;
+//! if let Some(report) = comparison.report {
+//!     println!("{} changed", report.changed.len());
+//! }
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+pub mod cache;
+pub mod compare;
+pub mod datetime;
+pub mod duplicates;
+pub mod errors;
+pub mod files;
+pub mod hash;
+pub mod manifest;
+pub mod report;
+pub mod stats;
+pub mod verify;
+pub mod walk;
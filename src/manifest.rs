@@ -0,0 +1,465 @@
+use std::{
+    collections::HashMap,
+    fs::read_to_string,
+    path::{Component, Path, PathBuf},
+    str::FromStr,
+};
+
+use crate::{
+    errors::ChksumError,
+    files::{detect_delimiter, FileInfo},
+    hash::{HashType, Hashes},
+};
+
+/// A single manifest row.
+#[derive(Debug, Clone)]
+pub struct ManifestEntry {
+    /// Path as logged.
+    pub path: PathBuf,
+    /// Path relative to the hashed directory.
+    pub relative_path: PathBuf,
+    /// Hashes as lowercase hex strings, one per hash type in the manifest.
+    pub hashes: Vec<String>,
+    /// File size in bytes, if logged.
+    pub size: Option<u64>,
+    /// File modification time as logged (`YYYY-MM-DD HH:mm:SS.fff`), if logged.
+    pub modified: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ManifestFormat {
+    /// CSV log written by chksum. Columns are identified by header,
+    /// with the hash algorithm as header for each hash column, e.g.
+    /// `FILENAME,SOURCEPATH,RELATIVEPATH,SIZE,MODIFIED,<HASHTYPE>[,<HASHTYPE>...],DATETIME`.
+    /// `SIZE` and `MODIFIED` are optional. The delimiter is detected from the header,
+    /// logs written by earlier versions are tab-separated.
+    Chksum,
+    /// GNU coreutils `sha256sum`/`md5sum`/`b3sum` format:
+    /// `<HASH>  <PATH>` (text mode) or `<HASH> *<PATH>` (binary mode).
+    /// Lines starting with `\` have escaped filenames (`\\`, `\n`, `\r`).
+    Coreutils,
+}
+
+impl std::fmt::Display for ManifestFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ManifestFormat::Chksum => write!(f, "chksum log"),
+            ManifestFormat::Coreutils => write!(f, "coreutils"),
+        }
+    }
+}
+
+/// Previously written checksum manifest, e.g. `checksums.csv` or `SHA256SUMS`.
+#[derive(Debug, Clone)]
+pub struct Manifest {
+    pub format: ManifestFormat,
+    /// Hash types in the order hashes are listed for each entry.
+    /// Coreutils manifests only have one.
+    pub hash_types: Vec<HashType>,
+    pub entries: Vec<ManifestEntry>,
+}
+
+impl Manifest {
+    /// Reads a manifest and detects its format.
+    ///
+    /// Coreutils manifests do not state the hash algorithm. It is inferred from
+    /// the manifest filename (e.g. `SHA256SUMS`, `files.md5`, `B3SUMS`) or, failing that,
+    /// from the hash length (32 = MD5, 40 = SHA-1). Otherwise `default_hash_type` is used.
+    pub fn from_path(path: &Path, default_hash_type: &HashType) -> Result<Self, ChksumError> {
+        let content = read_to_string(path)
+            .map_err(|err| ChksumError::OpenFileFailed((path.to_owned(), err)))?;
+        // byte order mark, e.g. logs written with '--bom'
+        let content = content.strip_prefix('\u{feff}').unwrap_or(&content);
+
+        let header = content
+            .lines()
+            .find(|line| !line.trim().is_empty())
+            .ok_or_else(|| ChksumError::ManifestParseError((path.to_owned(), "Empty manifest".to_owned())))?;
+        let delimiter = detect_delimiter(header);
+
+        match ChksumColumns::from_header(header, delimiter) {
+            Some(columns) => Self::parse_chksum(path, content, delimiter, columns),
+            None => Self::parse_coreutils(path, content, default_hash_type),
+        }
+    }
+
+    /// Returns manifest in the same form as `hash_files`, i.e.
+    /// key: `<RELATIVE_PATH>`, value: `(<PATH_AS_LOGGED>, [HASH, ...])`.
+    pub fn to_hashes(&self) -> Hashes {
+        self.entries
+            .iter()
+            .map(|entry| {
+                (
+                    entry.relative_path.to_owned(),
+                    (entry.path.to_owned(), entry.hashes.to_owned()),
+                )
+            })
+            .collect()
+    }
+
+    /// Returns logged size and modification time, keyed on relative path.
+    pub fn file_info(&self) -> HashMap<PathBuf, FileInfo> {
+        self.entries
+            .iter()
+            .map(|entry| {
+                (
+                    entry.relative_path.to_owned(),
+                    (entry.size, entry.modified.to_owned()),
+                )
+            })
+            .collect()
+    }
+
+    /// Creates a manifest from hashes returned by `hash_files`, using the relative path
+    /// (hash map key) for each entry. Entries are sorted on path.
+    pub fn from_hashes(
+        hashes: &Hashes,
+        hash_types: &[HashType],
+        format: ManifestFormat,
+    ) -> Self {
+        let mut entries: Vec<ManifestEntry> = hashes
+            .iter()
+            .map(|(path, (_full_path, hashes))| ManifestEntry {
+                path: path.to_owned(),
+                relative_path: path.to_owned(),
+                hashes: hashes.to_owned(),
+                size: None,
+                modified: None,
+            })
+            .collect();
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+
+        Self {
+            format,
+            hash_types: hash_types.to_vec(),
+            entries,
+        }
+    }
+
+    /// Paths are made relative via the `RELATIVEPATH` column if present.
+    /// Logs written by earlier versions lack this column, in which case the
+    /// longest common directory of all paths is stripped instead.
+    fn parse_chksum(path: &Path, content: &str, delimiter: u8, columns: ChksumColumns) -> Result<Self, ChksumError> {
+        let parse_error = |msg: String| ChksumError::ManifestParseError((path.to_owned(), msg));
+
+        let mut entries = csv::ReaderBuilder::new()
+            .delimiter(delimiter)
+            .has_headers(true)
+            .flexible(true)
+            .from_reader(content.as_bytes())
+            .into_records()
+            .map(|record| {
+                let fields = record.map_err(|err| parse_error(err.to_string()))?;
+                let line = fields.position().map(|p| p.line()).unwrap_or_default();
+                let relative_path = columns.relative_path.and_then(|c| fields.get(c));
+                let hashes = columns
+                    .hashes
+                    .iter()
+                    .map(|c| fields.get(*c).map(|h| h.to_ascii_lowercase()))
+                    .collect::<Option<Vec<_>>>();
+                match (fields.get(columns.path), hashes) {
+                    (Some(p), Some(h)) => Ok(ManifestEntry {
+                        path: PathBuf::from(p),
+                        relative_path: strip_cur_dir(Path::new(relative_path.unwrap_or(p))),
+                        hashes: h,
+                        size: columns.size.and_then(|c| fields.get(c)).and_then(|s| s.parse().ok()),
+                        modified: columns.modified
+                            .and_then(|c| fields.get(c))
+                            .filter(|m| !m.is_empty())
+                            .map(|m| m.to_string()),
+                    }),
+                    _ => Err(parse_error(format!("Too few columns on line {line}"))),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if columns.relative_path.is_none() {
+            if let Some(prefix) = common_dir(entries.iter().map(|e| e.path.as_path())) {
+                entries.iter_mut().for_each(|e| {
+                    if let Ok(p) = e.path.strip_prefix(&prefix) {
+                        e.relative_path = strip_cur_dir(p);
+                    }
+                });
+            }
+        }
+
+        Ok(Self {
+            format: ManifestFormat::Chksum,
+            hash_types: columns.hash_types,
+            entries,
+        })
+    }
+
+    fn parse_coreutils(path: &Path, content: &str, default_hash_type: &HashType) -> Result<Self, ChksumError> {
+        let entries = content
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#'))
+            .map(|(i, line)| {
+                // Leading backslash signals escaped filename
+                let (escaped, line) = match line.strip_prefix('\\') {
+                    Some(l) => (true, l),
+                    None => (false, line),
+                };
+                let parse_error = || ChksumError::ManifestParseError((path.to_owned(), format!("Improperly formatted line {}", i + 1)));
+
+                // '<HASH>  <PATH>' (text mode) or '<HASH> *<PATH>' (binary mode)
+                let (hash, rest) = line.split_once(' ').ok_or_else(parse_error)?;
+                let filename = rest
+                    .strip_prefix(' ')
+                    .or_else(|| rest.strip_prefix('*'))
+                    .ok_or_else(parse_error)?;
+
+                if hash.is_empty() || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+                    return Err(parse_error());
+                }
+
+                let filename = match escaped {
+                    true => unescape_filename(filename).ok_or_else(parse_error)?,
+                    false => filename.to_owned(),
+                };
+
+                Ok(ManifestEntry {
+                    path: PathBuf::from(&filename),
+                    relative_path: strip_cur_dir(Path::new(&filename)),
+                    hashes: vec![hash.to_ascii_lowercase()],
+                    size: None,
+                    modified: None,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let hash_type = HashType::from_sums_filename(path)
+            .or_else(|| match entries.first().map(|e| e.hashes[0].len()) {
+                Some(32) => Some(HashType::Md5),
+                Some(40) => Some(HashType::Sha1),
+                _ => None,
+            })
+            .unwrap_or(default_hash_type.to_owned());
+
+        if let Some(entry) = entries.iter().find(|e| e.hashes[0].len() != hash_type.len()) {
+            let msg = format!(
+                "Hash for '{}' does not have the expected length for {hash_type} ({} characters)",
+                entry.path.display(),
+                hash_type.len()
+            );
+            return Err(ChksumError::ManifestParseError((path.to_owned(), msg)));
+        }
+
+        Ok(Self {
+            format: ManifestFormat::Coreutils,
+            hash_types: vec![hash_type],
+            entries,
+        })
+    }
+
+    /// Returns hashes for `hash_type` in GNU coreutils format, one `<HASH>  <PATH>` line per entry,
+    /// readable by e.g. `sha256sum --check`. Filenames containing backslash,
+    /// newline or carriage return are escaped and the line prefixed with `\`.
+    ///
+    /// Returns `None` if the manifest does not contain `hash_type`.
+    pub fn to_coreutils_string(&self, hash_type: &HashType) -> Option<String> {
+        let index = self.hash_types.iter().position(|h| h == hash_type)?;
+        let sums = self.entries
+            .iter()
+            .map(|entry| {
+                let filename = entry.path.display().to_string();
+                match escape_filename(&filename) {
+                    Some(escaped) => format!("\\{}  {escaped}\n", entry.hashes[index]),
+                    None => format!("{}  {filename}\n", entry.hashes[index]),
+                }
+            })
+            .collect();
+
+        Some(sums)
+    }
+}
+
+/// Column indices for a chksum log, determined from its header.
+struct ChksumColumns {
+    path: usize,
+    relative_path: Option<usize>,
+    size: Option<usize>,
+    modified: Option<usize>,
+    hashes: Vec<usize>,
+    hash_types: Vec<HashType>,
+}
+
+impl ChksumColumns {
+    /// Returns `None` if the header does not contain
+    /// a path column and at least one hash column.
+    fn from_header(header: &str, delimiter: u8) -> Option<Self> {
+        let columns: Vec<&str> = header
+            .split(delimiter as char)
+            .map(|c| c.trim_matches('"'))
+            .collect();
+        let path = columns
+            .iter()
+            .position(|c| matches!(*c, "SOURCEPATH" | "TARGETPATH" | "PATH"))?;
+        let relative_path = columns.iter().position(|c| *c == "RELATIVEPATH");
+        let size = columns.iter().position(|c| *c == "SIZE");
+        let modified = columns.iter().position(|c| *c == "MODIFIED");
+        let (hashes, hash_types): (Vec<usize>, Vec<HashType>) = columns
+            .iter()
+            .enumerate()
+            .filter_map(|(i, c)| HashType::from_str(c).ok().map(|h| (i, h)))
+            .unzip();
+
+        if hashes.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            path,
+            relative_path,
+            size,
+            modified,
+            hashes,
+            hash_types,
+        })
+    }
+}
+
+/// Returns the longest directory path common to all paths.
+fn common_dir<'a>(mut paths: impl Iterator<Item = &'a Path>) -> Option<PathBuf> {
+    let mut common = paths.next()?.parent()?.to_owned();
+    for path in paths {
+        while !path.starts_with(&common) {
+            if !common.pop() {
+                return None;
+            }
+        }
+    }
+    Some(common)
+}
+
+/// Removes `.` components, e.g. `./dir/file.txt` becomes `dir/file.txt`,
+/// so that relative paths match those returned by `hash_files`.
+fn strip_cur_dir(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|c| !matches!(c, Component::CurDir))
+        .collect()
+}
+
+/// Escapes filename according to coreutils convention.
+/// Returns `None` if no escaping is required.
+fn escape_filename(filename: &str) -> Option<String> {
+    if !filename.contains(['\\', '\n', '\r']) {
+        return None;
+    }
+
+    Some(
+        filename
+            .replace('\\', "\\\\")
+            .replace('\n', "\\n")
+            .replace('\r', "\\r"),
+    )
+}
+
+/// Unescapes filename according to coreutils convention.
+/// Returns `None` for unknown escape sequences.
+fn unescape_filename(filename: &str) -> Option<String> {
+    let mut unescaped = String::with_capacity(filename.len());
+    let mut chars = filename.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next()? {
+                '\\' => unescaped.push('\\'),
+                'n' => unescaped.push('\n'),
+                'r' => unescaped.push('\r'),
+                _ => return None,
+            },
+            _ => unescaped.push(c),
+        }
+    }
+    Some(unescaped)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::files::CsvOptions;
+
+    use super::*;
+
+    #[test]
+    fn escape_round_trip() {
+        for filename in ["plain.txt", "back\\slash", "line\nbreak\r\n", "\\n literal"] {
+            let escaped = escape_filename(filename);
+            assert_eq!(escaped.is_some(), filename.contains(['\\', '\n', '\r']));
+            let escaped = escaped.unwrap_or(filename.to_owned());
+            assert!(!escaped.contains(['\n', '\r']));
+            assert_eq!(unescape_filename(&escaped).as_deref(), Some(filename));
+        }
+        assert_eq!(unescape_filename("unknown\\t"), None);
+        assert_eq!(unescape_filename("trailing\\"), None);
+    }
+
+    #[test]
+    fn coreutils_round_trip() {
+        let hash_types = vec![HashType::Md5];
+        let hashes: Hashes = ["a.txt", "dir/b c.txt", "back\\slash", "line\nbreak"]
+            .iter()
+            .enumerate()
+            .map(|(i, p)| (PathBuf::from(p), (PathBuf::from(p), vec![format!("{i:032x}")])))
+            .collect();
+        let manifest = Manifest::from_hashes(&hashes, &hash_types, ManifestFormat::Coreutils);
+        let content = manifest.to_coreutils_string(&HashType::Md5).unwrap();
+        assert_eq!(content.lines().count(), hashes.len());
+
+        let parsed = Manifest::parse_coreutils(Path::new("MD5SUMS"), &content, &HashType::Blake3).unwrap();
+        assert_eq!(parsed.hash_types, hash_types);
+        assert_eq!(parsed.to_hashes(), hashes);
+        assert_eq!(manifest.to_coreutils_string(&HashType::Sha256), None);
+    }
+
+    #[test]
+    fn parse_coreutils_rejects_invalid_lines() {
+        let hash = "a".repeat(64);
+        for content in [format!("{hash}\n"), format!("{hash} x.txt\n"), "xyz  x.txt\n".to_owned(), format!("{hash}  x.txt\n{}  y.txt\n", "a".repeat(32))] {
+            assert!(Manifest::parse_coreutils(Path::new("SHA256SUMS"), &content, &HashType::Blake3).is_err(), "{content:?}");
+        }
+    }
+
+    #[test]
+    fn parse_coreutils_strips_cur_dir() {
+        let hash = "a".repeat(64);
+        let content = format!("{hash}  ./dir/a.txt\n{hash} *./b.txt\n");
+        let manifest = Manifest::parse_coreutils(Path::new("SHA256SUMS"), &content, &HashType::Blake3).unwrap();
+
+        assert_eq!(manifest.hash_types, vec![HashType::Sha256]);
+        assert_eq!(manifest.entries[0].path, PathBuf::from("./dir/a.txt"));
+        assert_eq!(manifest.entries[0].relative_path, PathBuf::from("dir/a.txt"));
+        assert_eq!(manifest.entries[1].relative_path, PathBuf::from("b.txt"));
+    }
+
+    #[test]
+    fn parse_chksum_strips_cur_dir() {
+        let hash = "a".repeat(64);
+        let content = format!("PATH\tRELATIVEPATH\tSHA256\n./dir/a.txt\t./dir/a.txt\t{hash}\n");
+        let columns = ChksumColumns::from_header("PATH\tRELATIVEPATH\tSHA256", b'\t').unwrap();
+        let manifest = Manifest::parse_chksum(Path::new("checksums.csv"), &content, b'\t', columns).unwrap();
+
+        assert_eq!(manifest.entries[0].relative_path, PathBuf::from("dir/a.txt"));
+    }
+
+    #[test]
+    fn chksum_log_delimiter_round_trip() {
+        let path = std::env::temp_dir().join(format!("chksum_delimiter_test_{}.csv", std::process::id()));
+        let hash = "a".repeat(64);
+        let rows = vec![
+            vec!["FILENAME".to_owned(), "SOURCEPATH".to_owned(), "RELATIVEPATH".to_owned(), "SHA256".to_owned()],
+            vec!["a|b;c.txt".to_owned(), "dir/a|b;c.txt".to_owned(), "a|b;c.txt".to_owned(), hash.to_owned()],
+        ];
+        for (delimiter, quote_all) in [(b',', false), (b'\t', false), (b';', true), (b'|', false), (b'|', true), (b' ', false)] {
+            let csv = CsvOptions { delimiter, quote_all, bom: false }.to_csv(&rows).unwrap();
+            std::fs::write(&path, csv).unwrap();
+            let manifest = Manifest::from_path(&path, &HashType::Blake3).unwrap();
+            assert_eq!(manifest.format, ManifestFormat::Chksum, "{}", delimiter as char);
+            assert_eq!(manifest.hash_types, vec![HashType::Sha256]);
+            assert_eq!(manifest.entries[0].path, PathBuf::from("dir/a|b;c.txt"));
+            assert_eq!(manifest.entries[0].hashes, vec![hash.to_owned()]);
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+}
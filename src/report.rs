@@ -0,0 +1,167 @@
+use crate::stats::{ExtensionStats, Units};
+
+/// Table cell with optional sort key, e.g. size in bytes for a formatted size.
+#[derive(Debug, Clone)]
+pub struct Cell {
+    pub text: String,
+    pub sort_key: Option<String>,
+}
+
+impl Cell {
+    /// Formatted size, sorted on size in bytes.
+    pub fn size(bytes: u64) -> Self {
+        Self {
+            text: Units::from(bytes).to_string(),
+            sort_key: Some(bytes.to_string()),
+        }
+    }
+}
+
+impl From<String> for Cell {
+    fn from(text: String) -> Self {
+        Self { text, sort_key: None }
+    }
+}
+
+impl From<&str> for Cell {
+    fn from(text: &str) -> Self {
+        Self::from(text.to_owned())
+    }
+}
+
+/// Self-contained, static HTML report with no external assets.
+/// Tables can be sorted by clicking a column header.
+#[derive(Debug, Clone)]
+pub struct HtmlReport {
+    title: String,
+    sections: Vec<String>,
+}
+
+impl HtmlReport {
+    pub fn new(title: &str) -> Self {
+        Self {
+            title: title.to_owned(),
+            sections: Vec::new(),
+        }
+    }
+
+    /// Adds a two column table of labels and values.
+    pub fn totals(&mut self, heading: &str, totals: &[(&str, String)]) {
+        let rows = totals
+            .iter()
+            .map(|(label, value)| format!("<tr><th>{}</th><td>{}</td></tr>", escape(label), escape(value)))
+            .collect::<String>();
+        self.sections.push(format!(
+            "<h2>{}</h2>\n<table class=\"totals\">{rows}</table>",
+            escape(heading)
+        ));
+    }
+
+    /// Adds a sortable table. Empty tables are listed as "None".
+    /// Line breaks in cells are kept.
+    pub fn table(&mut self, heading: &str, headers: &[&str], rows: Vec<Vec<Cell>>) {
+        let heading = format!("<h2>{} ({})</h2>", escape(heading), rows.len());
+        if rows.is_empty() {
+            self.sections.push(format!("{heading}\n<p>None</p>"));
+            return;
+        }
+        let headers = headers
+            .iter()
+            .map(|h| format!("<th>{}</th>", escape(h)))
+            .collect::<String>();
+        let rows = rows
+            .iter()
+            .map(|row| {
+                let cells = row
+                    .iter()
+                    .map(|cell| match &cell.sort_key {
+                        Some(key) => format!("<td data-sort=\"{}\">{}</td>", escape(key), escape(&cell.text)),
+                        None => format!("<td>{}</td>", escape(&cell.text)),
+                    })
+                    .collect::<String>();
+                format!("<tr>{cells}</tr>\n")
+            })
+            .collect::<String>();
+        self.sections.push(format!(
+            "{heading}\n<table class=\"sortable\"><thead><tr>{headers}</tr></thead>\n<tbody>\n{rows}</tbody></table>"
+        ));
+    }
+
+    /// Adds total size and file count per file extension,
+    /// with bars for relative size, largest first.
+    pub fn extension_stats(&mut self, heading: &str, stats: &[ExtensionStats]) {
+        let total_size: u64 = stats.iter().map(|s| s.size).sum();
+        let mut stats = stats.to_vec();
+        stats.sort_by_key(|s| std::cmp::Reverse(s.size));
+        let rows = stats
+            .iter()
+            .map(|s| {
+                let relative_size = match total_size {
+                    0 => 0.0,
+                    _ => (s.size * 100) as f64 / total_size as f64,
+                };
+                format!(
+                    "<tr><td>{}</td><td>{}</td><td data-sort=\"{}\">{}</td><td data-sort=\"{relative_size}\">{relative_size:.3}%</td>\
+                    <td class=\"bar\"><div style=\"width: {relative_size:.3}%\"></div></td></tr>\n",
+                    escape(&s.extension),
+                    s.count,
+                    s.size,
+                    Units::from(s.size)
+                )
+            })
+            .collect::<String>();
+        self.sections.push(format!(
+            "<h2>{}</h2>\n<table class=\"sortable stats\"><thead><tr><th>Extension</th><th>Files</th><th>Size</th><th>%</th><th></th></tr></thead>\n<tbody>\n{rows}</tbody></table>",
+            escape(heading)
+        ));
+    }
+
+    pub fn to_html(&self) -> String {
+        format!(
+            "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n<style>{STYLE}</style>\n</head>\n<body>\n<h1>{title}</h1>\n{}\n<script>{SCRIPT}</script>\n</body>\n</html>\n",
+            self.sections.join("\n"),
+            title = escape(&self.title),
+        )
+    }
+}
+
+/// Escapes text for use in HTML content and attribute values.
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+const STYLE: &str = "
+body { font-family: sans-serif; margin: 2em; color: #222; }
+table { border-collapse: collapse; margin-bottom: 1em; }
+th, td { border: 1px solid #ccc; padding: 0.25em 0.5em; text-align: left; vertical-align: top; white-space: pre-line; }
+table.sortable th { cursor: pointer; background: #eee; }
+table.sortable th:hover { background: #ddd; }
+table.totals th { background: #eee; }
+td.bar { width: 20em; }
+td.bar div { background: #4a7ab5; height: 1em; }
+";
+
+// Sorts on `data-sort` if set, otherwise cell text. Numeric if both values are numbers.
+const SCRIPT: &str = "
+document.querySelectorAll('table.sortable th').forEach((th, column) => {
+  th.addEventListener('click', () => {
+    const tbody = th.closest('table').querySelector('tbody');
+    const ascending = th.dataset.order !== 'asc';
+    th.dataset.order = ascending ? 'asc' : 'desc';
+    const value = (row) => {
+      const cell = row.children[column];
+      return cell ? (cell.dataset.sort ?? cell.textContent) : '';
+    };
+    const rows = Array.from(tbody.rows).sort((a, b) => {
+      const [x, y] = [value(a), value(b)];
+      const order = (x !== '' && y !== '' && !isNaN(x) && !isNaN(y)) ? x - y : x.localeCompare(y);
+      return ascending ? order : -order;
+    });
+    rows.forEach((row) => tbody.appendChild(row));
+  });
+});
+";
@@ -0,0 +1,161 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use crate::{errors::ChksumError, files::file_stats};
+
+/// Total size and file count for a file extension.
+#[derive(Debug, Clone)]
+pub struct ExtensionStats {
+    /// File extension, or `< no ext >`.
+    pub extension: String,
+    /// Total size in bytes.
+    pub size: u64,
+    pub count: usize,
+}
+
+/// Returns total size and file count per file extension, sorted on count.
+/// Extensions are lower case unless `case_sensitive` is set.
+///
+/// Files that can not be read, e.g. deleted since they were hashed, are added to `errors`
+/// and skipped. If `errors` is `None` the first error is returned.
+pub fn extension_stats(
+    paths: &[PathBuf],
+    case_sensitive: bool,
+    mut errors: Option<&mut Vec<ChksumError>>,
+) -> Result<Vec<ExtensionStats>, ChksumError> {
+    let mut stats: HashMap<String, (u64, usize)> = HashMap::new();
+
+    for path in paths.iter() {
+        let (mut ext, size) = match file_stats(path) {
+            Ok((ext, size, _created, _modified)) => (ext.unwrap_or("< no ext >".to_owned()), size),
+            Err(err) => {
+                let err = ChksumError::ReadFileFailed((path.to_owned(), err));
+                match errors.as_deref_mut() {
+                    Some(errors) => {
+                        errors.push(err);
+                        continue;
+                    }
+                    None => return Err(err),
+                }
+            }
+        };
+
+        if !case_sensitive {
+            ext = ext.to_lowercase();
+        }
+
+        stats.entry(ext)
+            .and_modify(|v| {
+                v.0 += size;
+                v.1 += 1;
+            })
+            .or_insert((size, 1));
+    }
+
+    let mut stats_vec = stats.into_iter()
+        .map(|(extension, (size, count))| ExtensionStats { extension, size, count })
+        .collect::<Vec<_>>();
+    stats_vec.sort_by_key(|s| s.count);
+
+    Ok(stats_vec)
+}
+
+pub enum Units {
+    Bytes(u64),
+    Kilo(f64),
+    Mega(f64),
+    Giga(f64),
+    Tera(f64),
+}
+
+impl From<u64> for Units {
+    fn from(value: u64) -> Self {
+        match value as f64 {
+            _z @ ..1e3 => Self::Bytes(value),
+            z @ 1e3..1e6 => Self::Kilo(z / 1e3),
+            z @ 1e6..1e9 => Self::Mega(z / 1e6),
+            z @ 1e9..1e12 => Self::Giga(z / 1e9),
+            z @ 1e12..1e15 => Self::Tera(z / 1e12),
+            z => Self::Tera(z / 1e12),
+        }
+    }
+}
+
+impl Units {
+    /// Parses a size in bytes, optionally with a decimal unit suffix
+    /// as used for display: `B` (or `bytes`), `KB`, `MB`, `GB`, or `TB` (case-insensitive), e.g. `1.5GB`.
+    pub fn parse_bytes(value: &str) -> Result<u64, String> {
+        let value = value.trim();
+        let split = value
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(value.len());
+        let (number, unit) = value.split_at(split);
+        let factor = match unit.trim().to_ascii_uppercase().as_str() {
+            "" | "B" | "BYTES" => 1e0,
+            "K" | "KB" => 1e3,
+            "M" | "MB" => 1e6,
+            "G" | "GB" => 1e9,
+            "T" | "TB" => 1e12,
+            _ => return Err(format!("Invalid size unit '{unit}', expected B, KB, MB, GB, or TB")),
+        };
+        number
+            .parse::<f64>()
+            .map(|n| (n * factor).round() as u64)
+            .map_err(|_| format!("Invalid size '{value}'"))
+    }
+}
+
+impl std::fmt::Display for Units {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Units::Bytes(n) => write!(f, "{n} bytes"),
+            Units::Kilo(fl) => write!(f, "{fl:.2}KB", ),
+            Units::Mega(fl) => write!(f, "{fl:.2}MB", ),
+            Units::Giga(fl) => write!(f, "{fl:.2}GB", ),
+            Units::Tera(fl) => write!(f, "{fl:.2}TB", ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_bytes() {
+        assert_eq!(Units::parse_bytes("0"), Ok(0));
+        assert_eq!(Units::parse_bytes("512"), Ok(512));
+        assert_eq!(Units::parse_bytes("512B"), Ok(512));
+        assert_eq!(Units::parse_bytes("16kb"), Ok(16_000));
+        assert_eq!(Units::parse_bytes(" 1.5 GB "), Ok(1_500_000_000));
+        assert_eq!(Units::parse_bytes("2T"), Ok(2_000_000_000_000));
+        assert!(Units::parse_bytes("1.5XB").is_err());
+        assert!(Units::parse_bytes("MB").is_err());
+        assert!(Units::parse_bytes("-1MB").is_err());
+    }
+
+    #[test]
+    fn extension_stats_skips_missing() {
+        let dir = std::env::temp_dir().join(format!("chksum_stats_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let paths = vec![dir.join("a.TXT"), dir.join("b.txt"), dir.join("deleted.txt")];
+        std::fs::write(&paths[0], b"abc").unwrap();
+        std::fs::write(&paths[1], b"de").unwrap();
+
+        assert!(extension_stats(&paths, false, None).is_err());
+
+        let mut errors = Vec::new();
+        let stats = extension_stats(&paths, false, Some(&mut errors)).unwrap();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(stats.len(), 1);
+        assert_eq!((stats[0].extension.as_str(), stats[0].size, stats[0].count), ("txt", 5, 2));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn parse_bytes_round_trip() {
+        for value in [0, 999, 1_000, 16_000_000, 1_500_000_000, 3_250_000_000_000] {
+            assert_eq!(Units::parse_bytes(&Units::from(value).to_string()), Ok(value));
+        }
+    }
+}
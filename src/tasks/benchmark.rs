@@ -0,0 +1,92 @@
+use std::{
+    path::PathBuf,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Instant,
+};
+
+use clap::ArgMatches;
+
+use chksum::{
+    errors::ChksumError,
+    files::drop_cached,
+    hash::{hash_files, hash_types_to_string, HashContext, HashEvent, HashOptions, HashType, Hashes, IoStrategy},
+    stats::Units,
+};
+
+use super::{hash_options, print_errors};
+
+/// Hashes `paths` once per I/O strategy, with and without `--fadvise` on Linux,
+/// and prints the throughput for each. Files are dropped from the page cache
+/// before each pass where supported, so that all passes read from disk.
+/// Files that can not be read are skipped.
+pub fn run(args: &ArgMatches, paths: &[PathBuf], hash_types: &[HashType]) -> std::io::Result<()> {
+    let base = hash_options(args);
+    let advice: &[bool] = if cfg!(target_os = "linux") { &[false, true] } else { &[false] };
+    let strategies = [IoStrategy::Auto, IoStrategy::Buffered, IoStrategy::Mmap]
+        .into_iter()
+        .flat_map(|io| advice.iter().map(move |fadvise| (io, *fadvise)))
+        .map(|(io, fadvise)| HashOptions { io, fadvise, ..base.clone() });
+
+    println!("\n[ BENCHMARK | {} ]", hash_types_to_string(hash_types));
+    println!("{:>28} {:>10} {:>10} {:>12}", "I/O", "SIZE", "TIME", "THROUGHPUT");
+
+    let mut reference: Option<Hashes> = None;
+    let mut errors: Vec<ChksumError> = Vec::new();
+    let mut cached = false;
+    for options in strategies {
+        // same files fail in each pass, only those from the last are listed
+        errors.clear();
+        // unreadable files are skipped anyway
+        cached |= !paths
+            .iter()
+            .all(|path| drop_cached(path).unwrap_or(true));
+
+        let bytes = AtomicU64::new(0);
+        let on_hashed = |event: &HashEvent| {
+            bytes.fetch_add(event.size.unwrap_or_default(), Ordering::Relaxed);
+        };
+        let started = Instant::now();
+        let context = HashContext {
+            cache: None,
+            errors: Some(&mut errors),
+            on_hashed: Some(&on_hashed),
+        };
+        let hashes = hash_files(paths, hash_types, &options, None, context)?;
+        let seconds = started.elapsed().as_secs_f64();
+        let bytes = bytes.into_inner();
+
+        let label = match options.io {
+            IoStrategy::Buffered => format!("{} ({})", options.io, Units::from(options.aligned_buffer_size() as u64)),
+            io => io.to_string(),
+        };
+        println!(
+            "{:>28} {:>10} {:>8.2} s {:>7.1} MB/s",
+            if options.fadvise { format!("{label} + fadvise") } else { label },
+            Units::from(bytes).to_string(),
+            seconds,
+            bytes as f64 / 1e6 / seconds.max(f64::EPSILON)
+        );
+
+        match &reference {
+            Some(reference) => {
+                let differing = reference
+                    .iter()
+                    .filter(|(path, hash)| hashes.get(*path) != Some(hash))
+                    .count();
+                if differing > 0 {
+                    println!("(!) Hashes differ from the first pass for {differing} files. Files may have changed during the benchmark.");
+                }
+            }
+            None => reference = Some(hashes),
+        }
+    }
+
+    if cached {
+        println!("\n(!) Files could not be dropped from the page cache on this platform. Passes after the first may read from memory.");
+    }
+    if !errors.is_empty() {
+        print_errors(&errors.iter().collect::<Vec<_>>());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,418 @@
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use clap::ArgMatches;
+use serde_json::json;
+
+use chksum::{
+    compare::{compare_trees, comparison_hash_types, CompareOptions, Comparison, TreeContext, TreeSource},
+    errors::{errors_to_log, ChksumError},
+    files::{checksum_header, checksum_row, file_info, writefile, CsvLog, LogLevel},
+    hash::{hash_types_to_string, HashEvent, HashType, OnHashed},
+    manifest::{Manifest, ManifestFormat},
+};
+
+use super::print_written;
+
+/// Hashes `source-dir`, or reads `source-manifest`, and compares it with `target-dir`
+/// or `target-manifest` if set. Logs, reports, and coreutils manifests are written as set.
+/// Exits with the exit code for the first category that applies.
+///
+/// `hash_types` are ignored if either side is a manifest,
+/// in which case the manifest's hash types are used.
+pub fn run(args: &ArgMatches, hash_types: &[HashType], started: Instant) -> std::io::Result<()> {
+    let format = args.get_one::<String>("format").map(|f| f.as_str()).unwrap_or("text");
+    let json = format == "json";
+    let ndjson = format == "ndjson";
+    // stdout is reserved for JSON output
+    let quiet = json || ndjson;
+    let overwrite = super::overwrite(args);
+
+    // Either side of the comparison may be a previously written manifest.
+    // Manifests determine hash algorithm, since they can not be re-hashed.
+    let mut manifests: Vec<Option<Manifest>> = Vec::new();
+    for (dirtype, arg) in [("SOURCE", "source-manifest"), ("TARGET", "target-manifest")] {
+        let manifest = match args.get_one::<PathBuf>(arg) {
+            Some(manifest_path) => {
+                if !quiet {
+                    print!("[ {dirtype} | {} ] Reading manifest...", manifest_path.display());
+                    std::io::stdout().flush()?;
+                }
+                let manifest = Manifest::from_path(manifest_path, &hash_types[0])?;
+                if !quiet {
+                    println!(
+                        " Done ({} files, {}, {})",
+                        manifest.entries.len(),
+                        manifest.format,
+                        hash_types_to_string(&manifest.hash_types)
+                    );
+                }
+                Some(manifest)
+            }
+            None => None,
+        };
+        manifests.push(manifest);
+    }
+    let target_manifest = manifests.pop().flatten();
+    let source_manifest = manifests.pop().flatten();
+
+    // !!! input paths result in error on Windows 10 if they end in '/' AND contains space,
+    // !!! however using '\' (powershell) works...??? Clap bug? Powershell bug?
+    // Directories to hash, a target manifest is only read if target-dir is not set
+    let source_dir = args.get_one::<PathBuf>("source-dir").filter(|_| source_manifest.is_none());
+    let target_dir = args.get_one::<PathBuf>("target-dir");
+    let source = match (&source_manifest, source_dir) {
+        (Some(manifest), _) => TreeSource::Manifest(manifest),
+        (None, dir) => TreeSource::Dir(dir.unwrap()), // required unless verify or source-manifest
+    };
+    let target = match (target_dir, &target_manifest) {
+        (Some(dir), _) => Some(TreeSource::Dir(dir)),
+        (None, Some(manifest)) => Some(TreeSource::Manifest(manifest)),
+        (None, None) => None,
+    };
+    let comparing = target.is_some();
+
+    let continue_on_error = *args.get_one::<bool>("continue-on-error").unwrap();
+    let options = CompareOptions {
+        hash_types: comparison_hash_types(source, target, hash_types)?,
+        walk_options: super::walk_options(args),
+        hash_options: super::hash_options(args),
+        continue_on_error,
+    };
+    let hash_types = &options.hash_types;
+    let verbose = *args.get_one::<bool>("verbose").unwrap() && !quiet;
+
+    let log_level = LogLevel::from(*args.get_one::<bool>("log").unwrap());
+    let write_sums = *args.get_one::<bool>("sums").unwrap();
+    let cache_stats = *args.get_one::<bool>("cache-stats").unwrap();
+
+    // Checksum logs for directories are written while hashing,
+    // for manifests once all entries are read
+    let csv = super::csv_options(args);
+    let stream_log = |dir: Option<&PathBuf>, name: &str, header: &[String]| -> std::io::Result<Option<CsvLog>> {
+        if dir.is_none() || log_level != LogLevel::Normal {
+            return Ok(None);
+        }
+        let path = super::log_dir(args)?.join(name);
+        let log = CsvLog::create(&path, header, &csv, overwrite)?;
+        if log.is_none() {
+            print_written(Ok(false), &path, quiet);
+        }
+        Ok(log)
+    };
+    let source_log = stream_log(
+        source_dir,
+        if comparing { "checksums_source.csv" } else { "checksums.csv" },
+        &checksum_header("SOURCEPATH", hash_types),
+    )?;
+    let target_log = stream_log(target_dir, "checksums_target.csv", &checksum_header("TARGETPATH", hash_types))?;
+
+    // NDJSON events are printed and checksum logs written as soon as each file is hashed
+    let on_event = |event: &HashEvent, dirtype: &str, root: Option<&PathBuf>, log: &Option<CsvLog>| {
+        if verbose {
+            super::print_hashed(event, dirtype);
+        }
+        if ndjson {
+            let mut json = event.to_json(hash_types);
+            json["dirtype"] = dirtype.trim().into();
+            println!("{json}");
+        }
+        if let (Some(root), Some(log), Some(hashes)) = (root, log, event.hashes) {
+            let relative_path = event.path.strip_prefix(root).unwrap_or(event.path);
+            log.append(&checksum_row(event.path, relative_path, file_info(event.path), hashes));
+        }
+    };
+    let source_events = |event: &HashEvent| on_event(event, "SOURCE", source_dir, &source_log);
+    let target_events = |event: &HashEvent| on_event(event, "TARGET", target_dir, &target_log);
+
+    let mut source_cache = source_dir.and_then(|dir| super::hash_cache(args, dir));
+    let mut target_cache = target_dir.and_then(|dir| super::hash_cache(args, dir));
+
+    if !quiet {
+        for (dirtype, dir) in [("SOURCE", source_dir), ("TARGET", target_dir)] {
+            if let Some(dir) = dir {
+                println!("[ {dirtype} | {} ] Compiling hashes...", dir.display());
+            }
+        }
+    }
+    let Comparison { source: source_tree, target: target_tree, report, .. } = compare_trees(
+        source,
+        target,
+        &options,
+        TreeContext {
+            cache: source_cache.as_mut(),
+            on_hashed: (verbose || ndjson || source_log.is_some()).then_some(&source_events as OnHashed),
+        },
+        TreeContext {
+            cache: target_cache.as_mut(),
+            on_hashed: (verbose || ndjson || target_log.is_some()).then_some(&target_events as OnHashed),
+        },
+    )?;
+    let source_count = source_tree.hashes.len();
+
+    // corrupt files found via cache (--rehash)
+    let mut cache_corrupt_count = 0;
+    let sides = [
+        ("SOURCE", source_dir, Some(&source_tree), source_cache.as_mut()),
+        ("TARGET", target_dir, target_tree.as_ref(), target_cache.as_mut()),
+    ];
+    for (dirtype, dir, tree, cache) in sides {
+        let (Some(dir), Some(tree)) = (dir, tree) else {
+            continue;
+        };
+        let label = format!("{dirtype} | {}", dir.display());
+        if !quiet {
+            println!("[ {label} ] Done ({} files)\n", tree.hashes.len());
+        }
+        if let Some(cache) = cache {
+            super::finish_cache(cache, &label, cache_stats, quiet);
+            cache_corrupt_count += cache.corrupt.len();
+        }
+    }
+    for log in [source_log, target_log].into_iter().flatten() {
+        let path = log.path().to_owned();
+        print_written(log.finish().map(|_| true), &path, quiet);
+    }
+
+    let errors: Vec<&ChksumError> = source_tree.errors
+        .iter()
+        .chain(target_tree.iter().flat_map(|tree| tree.errors.iter()))
+        .collect();
+
+    if let (Some(target_tree), Some(report)) = (&target_tree, &report) {
+        let source_path = |path: &Path| source_tree.full_path(path).expect("Failed to get source path");
+        let target_path = |path: &Path| target_tree.full_path(path).expect("Failed to get target path");
+
+        if json {
+            println!("{}", report.to_json(&source_tree, target_tree, hash_types));
+        } else if !ndjson {
+            println!("Result: {}/{} files match", report.matched.len(), source_count);
+
+            if !report.legacy.is_empty() {
+                println!("\n{} files match a legacy digest (logged by chksum up to 2024-11-06):", report.legacy.len());
+                for (i, path) in report.legacy.iter().enumerate() {
+                    println!("  [ LEGACY  {:5} ] {}", i + 1, target_path(path).display())
+                }
+            }
+
+            print!("\n{} files missing in target", report.missing.len());
+            if report.missing.is_empty() {
+                println!();
+            } else {
+                println!(":");
+                for (i, path) in report.missing.iter().enumerate() {
+                    println!("  [ MISSING {:5} ] {}", i + 1, source_path(path).display())
+                }
+            }
+
+            print!("\n{} files moved/renamed in target", report.moved.len());
+            if report.moved.is_empty() {
+                println!();
+            } else {
+                println!(":");
+                for (i, (source_path, target_path)) in report.moved.iter().enumerate() {
+                    println!(
+                        "  [ MOVED   {:5} ] {} -> {}",
+                        i + 1,
+                        source_path.display(),
+                        target_path.display()
+                    )
+                }
+            }
+
+            print!("\n{} files changed in target", report.changed.len());
+            let mut new_source = 0;
+            let mut new_target = 0;
+            if report.changed.is_empty() {
+                println!();
+            } else {
+                println!(":");
+                for (i, path) in report.changed.iter().enumerate() {
+                    // Modification date and size are read from disk,
+                    // or from manifests if logged
+                    let (source_size, source_modified) = source_tree.file_info(path);
+                    let source_modified_str = source_modified.as_deref().unwrap_or("-");
+                    let source_size_str = source_size
+                        .map(|n| n.to_string())
+                        .unwrap_or("-".to_owned());
+
+                    let (target_size, target_modified) = target_tree.file_info(path);
+                    let target_modified_str = target_modified.as_deref().unwrap_or("-");
+                    let target_size_str = target_size
+                        .map(|n| n.to_string())
+                        .unwrap_or("-".to_owned());
+
+                    println!("  [ CHANGED {:5} ] {}", i + 1, target_path(path).display());
+                    // datetime strings sort chronologically
+                    let (sentinel_source, sentinel_target) = match (&source_modified, &target_modified) {
+                        (Some(s), Some(t)) if s > t => {
+                            new_source += 1;
+                            ("->", "  ")
+                        },
+                        (Some(_), Some(_)) => {
+                            new_target += 1;
+                            ("  ", "->")
+                        },
+                        _ => ("  ", "  "),
+                    };
+                    println!(
+                        " {sentinel_source} SOURCE {source_modified_str:23} {source_size_str:>12} bytes {}",
+                        source_path(path).display()
+                    );
+                    println!(
+                        " {sentinel_target} TARGET {target_modified_str:23} {target_size_str:>12} bytes {}",
+                        target_path(path).display()
+                    );
+                }
+                println!("{new_source:4}/{} are newer in SOURCE", report.changed.len());
+                println!("{new_target:4}/{} are newer in TARGET", report.changed.len());
+            }
+
+            print!("\n{} files corrupt in target (hash changed, size and modification time did not)", report.corrupt.len());
+            if report.corrupt.is_empty() {
+                println!();
+            } else {
+                println!(":");
+                for (i, path) in report.corrupt.iter().enumerate() {
+                    println!("  [ CORRUPT {:5} ] {}", i + 1, target_path(path).display())
+                }
+            }
+
+            print!("\n{} files not in source", report.ignored.len());
+            if report.ignored.is_empty() {
+                println!();
+            } else {
+                println!(":");
+                for (i, path) in report.ignored.iter().enumerate() {
+                    println!("  [ IGNORED {:5} ] {}", i + 1, target_path(path).display())
+                }
+            }
+
+            if continue_on_error {
+                super::print_errors(&errors);
+            }
+
+            println!("\nSummary");
+            println!("{}/{} files match", report.matched.len(), source_count);
+            if !report.legacy.is_empty() {
+                println!("{:4} files match a legacy digest", report.legacy.len());
+            }
+            println!("{:4} files missing in target", report.missing.len());
+            println!("{:4} files moved/renamed in target", report.moved.len());
+            println!("{:4} files changed in target", report.changed.len());
+            println!("{:4} files corrupt in target", report.corrupt.len());
+            println!("{:4} files missing in source", report.ignored.len());
+            if continue_on_error {
+                println!("{:4} files could not be read", errors.len());
+            }
+        }
+    } else if continue_on_error && !quiet {
+        super::print_errors(&errors);
+    }
+
+    // files deleted since they were hashed are skipped in file type statistics with --continue-on-error
+    let report_errors = match (&target_tree, &report) {
+        (Some(target_tree), Some(report)) => {
+            super::report::comparison(args, &source_tree, target_tree, report, hash_types, quiet)?
+        }
+        _ => Vec::new(),
+    };
+    if !report_errors.is_empty() && !quiet {
+        super::print_errors(&report_errors.iter().collect::<Vec<_>>());
+    }
+    let errors: Vec<&ChksumError> = errors.into_iter().chain(report_errors.iter()).collect();
+
+    if ndjson {
+        let mut summary = json!({
+            "event": "summary",
+            "hash_types": hash_types.iter().map(|h| h.to_string()).collect::<Vec<_>>(),
+            "files": source_count + target_tree.as_ref().map(|t| t.hashes.len()).unwrap_or_default(),
+            "errors": errors.len(),
+            "duration_ms": started.elapsed().as_secs_f64() * 1000.0,
+        });
+        if let (Some(target_tree), Some(report)) = (&target_tree, &report) {
+            summary["totals"] = report.totals_json(&source_tree, target_tree);
+        }
+        println!("{summary}");
+    }
+
+    if log_level == LogLevel::Normal {
+        let log_dir = super::log_dir(args)?;
+
+        // Write all hashes as CSV to disk for manifests, directories are logged while hashing
+        if source_dir.is_none() {
+            let path = log_dir.join(if comparing { "checksums_source.csv" } else { "checksums.csv" });
+            let log = source_tree.to_log(hash_types, "SOURCEPATH");
+            print_written(writefile(&csv.to_csv(&log)?, &path, overwrite), &path, quiet);
+        }
+
+        if let (Some(target_tree), Some(report)) = (&target_tree, &report) {
+            if target_dir.is_none() {
+                let path = log_dir.join("checksums_target.csv");
+                let log = target_tree.to_log(hash_types, "TARGETPATH");
+                print_written(writefile(&csv.to_csv(&log)?, &path, overwrite), &path, quiet);
+            }
+
+            // matched files are always logged
+            for log in report.to_logs(&source_tree, target_tree, hash_types) {
+                let path = log_dir.join(log.file_name);
+                if !log.is_empty() || log.category == "matched" {
+                    print_written(writefile(&csv.to_csv(&log.rows)?, &path, overwrite), &path, quiet);
+                } else if !quiet {
+                    println!("No {} files. Skipping CSV.", log.category)
+                }
+            }
+        }
+
+        if !errors.is_empty() {
+            let log_errors_path = log_dir.join(Path::new("errors.csv"));
+            print_written(
+                writefile(&csv.to_csv(&errors_to_log(&errors))?, &log_errors_path, overwrite),
+                &log_errors_path,
+                quiet
+            );
+        }
+    }
+
+    if write_sums {
+        let log_dir = super::log_dir(args)?;
+        let manifest = Manifest::from_hashes(&source_tree.hashes, hash_types, ManifestFormat::Coreutils);
+
+        // one manifest per hash type
+        for hash_type in hash_types.iter() {
+            if hash_type.chunk_size().is_some() {
+                eprintln!("(!) Not writing coreutils manifest for {hash_type}: chunked hashes differ from those of e.g. sha256sum");
+                continue;
+            }
+            let sums_path = log_dir.join(hash_type.sums_filename());
+            let sums = manifest
+                .to_coreutils_string(hash_type)
+                .expect("Failed to retrieve hashes for manifest");
+            print_written(writefile(&sums, &sums_path, overwrite), &sums_path, quiet);
+        }
+    }
+
+    let corrupt_count = report.as_ref().map(|r| r.corrupt.len()).unwrap_or_default();
+    if corrupt_count > 0 || cache_corrupt_count > 0 {
+        std::process::exit(crate::EXIT_CORRUPT);
+    }
+    if !errors.is_empty() {
+        std::process::exit(crate::EXIT_FILE_ERRORS);
+    }
+    if let Some(report) = &report {
+        let differences = [
+            ("missing", report.missing.len()),
+            ("changed", report.changed.len()),
+            ("new", report.ignored.len()),
+            ("moved", report.moved.len()),
+        ];
+        if differences.iter().any(|(category, count)| *count > 0 && super::fail_on(args, category)) {
+            std::process::exit(crate::EXIT_DIFFERENCES);
+        }
+    }
+
+    Ok(())
+}
@@ -1,66 +1,91 @@
-use std::{
-    collections::HashMap,
-    env::current_dir,
-    fs::create_dir_all,
-    io::Write,
-    path::{Path, PathBuf},
-};
+use std::path::{Path, PathBuf};
 
 use clap::ArgMatches;
 
-use crate::{
+use chksum::{
+    duplicates::{find_duplicates, DuplicateOptions},
+    errors::{errors_to_log, ChksumError},
     files::{confirm, writefile, LogLevel},
-    hash::{hash2path, hash_files, HashType},
+    hash::{HashContext, HashEvent, HashType, OnHashed},
+};
+
+use super::{
+    csv_options, finish_cache, hash_cache, hash_options, print_errors, print_hashed, print_written,
+    walk_options,
 };
 
-pub fn run(
-    args: &ArgMatches,
-    paths: &[PathBuf],
-    source_hashes: &HashMap<PathBuf, (PathBuf, String)>, // partial hashes for pruning unique hashes quickly
-    hash_type: &HashType,
-    verbose: bool,
-) -> std::io::Result<()> {
+/// Finds duplicate files in `source-dir`. All files are first hashed partially
+/// (see `partial-hash-size`), then only files sharing a partial hash are hashed in full
+/// with the first of `hash_types`.
+pub fn run(args: &ArgMatches, hash_types: &[HashType]) -> std::io::Result<()> {
+    let source_dir = args.get_one::<PathBuf>("source-dir").unwrap(); // required unless verify or source-manifest
+    let hash_type = &hash_types[0];
     let log_level = LogLevel::from(*args.get_one::<bool>("log").unwrap());
+    let overwrite = super::overwrite(args);
+    let verbose = *args.get_one::<bool>("verbose").unwrap();
+    let continue_on_error = *args.get_one::<bool>("continue-on-error").unwrap();
+    let options = DuplicateOptions {
+        walk_options: walk_options(args),
+        hash_type: hash_type.to_owned(),
+        partial_hash_type: args.get_one::<HashType>("partial-hash-algo").unwrap_or(hash_type).to_owned(),
+        partial_hash_size: *args.get_one::<usize>("partial-hash-size").unwrap(), // clap default 1000
+        hash_options: hash_options(args),
+    };
+
+    if !hash_type.is_cryptographic() {
+        println!("(!) {hash_type} is not collision resistant. Listed duplicates may differ in content.");
+    }
 
-    print!("Pruning unique hashes...");
-    std::io::stdout().flush()?;
-    let pruned_hashes: HashMap<String, Vec<PathBuf>> = hash2path(source_hashes, true);
-    let pruned_paths: Vec<_> = pruned_hashes
-        .values()
-        .flatten()
-        .cloned()
-        .collect();
+    let mut cache = hash_cache(args, source_dir);
+    // files that could not be read, from quick check and full hashes
+    let mut errors: Vec<ChksumError> = Vec::new();
+
+    // Read only part of each file, then only hash files sharing a partial hash in full
+    println!(
+        "[ DUPL | {} ] Compiling partial hashes ({} bytes), then full hashes for files sharing a partial hash...",
+        source_dir.display(),
+        options.partial_hash_size
+    );
+    let on_hashed = |event: &HashEvent| print_hashed(event, " DUPL ");
+    let duplicates = find_duplicates(
+        source_dir,
+        &options,
+        HashContext {
+            cache: cache.as_mut(),
+            errors: continue_on_error.then_some(&mut errors),
+            on_hashed: verbose.then_some(&on_hashed as OnHashed),
+        },
+    )?;
     println!(
-        " Done (pruned {} unique files)",
-        source_hashes.len() - pruned_paths.len()
+        "Done ({} files, {} sharing a partial hash hashed in full)\n",
+        duplicates.files,
+        duplicates.hashed_in_full
     );
+    let file_count = duplicates.files;
+    let groups = duplicates.groups;
 
-    println!("\nHashing remaining files in full...");
-    let duplicate_hashes = hash_files(&pruned_paths, " DUPL ", &hash_type, verbose, None, None)?;
-    println!("Done ({} files)\n", duplicate_hashes.len());
+    if let Some(cache) = cache.as_mut() {
+        finish_cache(cache, " DUPL ", *args.get_one::<bool>("cache-stats").unwrap(), false);
+    }
 
-    // Duplicate files, somewhat odd structure: HASH\t\FILE1\tFILE2\t... (columns will vary depending on number of duplicates)
-    let mut log_duplicates: Vec<String> = Vec::new();
+    // Duplicate files, somewhat odd structure: HASH, FILE1, FILE2, ... (columns will vary depending on number of duplicates)
+    let mut log_duplicates: Vec<Vec<String>> = Vec::new();
     // For determining number of columns/headers
     let mut log_duplicates_max = 0_usize;
     let mut log_duplicates_ext = "<NONE>".to_owned();
 
-    let duplicate_paths = hash2path(&duplicate_hashes, true);
     let mut dupe_hash_count = 0;
     let mut dupe_paths: Vec<PathBuf> = Vec::new();
     let mut uniq_paths: Vec<PathBuf> = Vec::new();
 
-    // Unique files removed above so no further check
-    for (hash, paths) in duplicate_paths.iter() {
-        // tab separated string (hash\tpath1\tpath2...) for log
-        log_duplicates.push(format!(
-            "{hash}\t{}",
-            paths
-                .iter()
-                .map(|p| p.display().to_string())
-                .collect::<Vec<_>>()
-                .join("\t")
-        ));
+    for group in groups.iter() {
+        let (hash, paths) = (&group.hash, &group.paths);
+        // hash, path1, path2... for log
+        log_duplicates.push(
+            std::iter::once(hash.to_owned())
+                .chain(paths.iter().map(|p| p.display().to_string()))
+                .collect()
+        );
 
         if paths.len() > log_duplicates_max {
             log_duplicates_max = paths.len();
@@ -78,13 +103,13 @@ pub fn run(
             paths
                 .first()
                 .map(|p| p.to_owned())
-                .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::Other, "Failed to retrive first duplicate path."))?
+                .ok_or_else(|| std::io::Error::other("Failed to retrive first duplicate path."))?
             );
         dupe_paths.extend(paths.to_owned());
         println!(
             "[{:5} | {} HASH: {}]",
             dupe_hash_count,
-            hash_type.to_string(),
+            hash_type,
             hash
         );
 
@@ -93,55 +118,74 @@ pub fn run(
         }
     }
 
+    let errors: Vec<&ChksumError> = errors.iter().collect();
+    if continue_on_error {
+        print_errors(&errors);
+    }
+
     println!("\nSummary (filtered to specified parameters):");
-    println!("  Files, total:           {}", paths.len());
+    println!("  Files, total:           {}", file_count);
     println!("  Duplicate files, total: {}", dupe_paths.len());
     println!("  ...with unique hashes:  {}", dupe_hash_count);
+    if continue_on_error {
+        println!("  Could not be read:      {}", errors.len());
+    }
 
     if log_level == LogLevel::Normal {
-        let log_dir = match args.get_one::<PathBuf>("log-dir") {
-            Some(d) => d.to_owned(), // must exist
-            None => {
-                let dir = current_dir()?.join("chksum_logs");
-                create_dir_all(&dir)?;
-                dir
-            }
-        };
+        let log_dir = super::log_dir(args)?;
+        let csv = csv_options(args);
+
+        if !errors.is_empty() {
+            let log_errors_path = log_dir.join(Path::new("errors.csv"));
+            print_written(writefile(&csv.to_csv(&errors_to_log(&errors))?, &log_errors_path, overwrite), &log_errors_path, false);
+        }
 
         let log_path = log_dir.join(Path::new("duplicates.csv"));
 
-        if log_duplicates_max > 0 {
-            // will only be > 0 if duplicates is set AND duplicate files have been found
-            if log_duplicates_max > 50 {
+        // will only be > 0 if duplicates is set AND duplicate files have been found
+        let write_log = match log_duplicates_max {
+            0 => false,
+            1..=50 => true,
+            _ => {
                 let msg = format!(
                     "(!) File type '{log_duplicates_ext}' results in {log_duplicates_max} columns (one per duplicated file). Write log anyway?"
                 );
-                if confirm(&msg)? == false {
+                let confirmed = confirm(&msg, true)?;
+                if !confirmed {
                     println!("User aborted writing log.");
-                    return Ok(());
                 }
+                confirmed
             }
-            let headers = format!(
-                "{}HASH\t{}",
-                hash_type.to_string(),
-                (0..log_duplicates_max)
-                    .into_iter()
-                    .map(|i| format!("FILE{}\t", i + 1))
-                    .collect::<String>()
-            );
-            match writefile(
-                &format!("{}\n", vec![headers, log_duplicates.join("\n")].join("\n")),
-                &log_path,
-            ) {
-                Ok(true) => println!("Wrote {}", log_path.display()),
-                Ok(false) => println!("Aborted writing CSV."),
-                Err(err) => println!("(!) Failed to write {}: {err}", log_path.display()),
-            }
+        };
+
+        if write_log {
+            let headers: Vec<String> = std::iter::once(format!("{hash_type}HASH"))
+                .chain((0..log_duplicates_max).map(|i| format!("FILE{}", i + 1)))
+                .collect();
+            // pad rows to the same number of columns
+            log_duplicates
+                .iter_mut()
+                .for_each(|row| row.resize(headers.len(), String::new()));
+            log_duplicates.insert(0, headers);
+            print_written(writefile(&csv.to_csv(&log_duplicates)?, &log_path, overwrite), &log_path, false);
         }
     }
 
+    // files deleted since they were hashed are skipped in file type statistics with --continue-on-error
+    let report_errors = super::report::duplicates(args, file_count, &groups, hash_type, &errors)?;
+    if !report_errors.is_empty() {
+        print_errors(&report_errors.iter().collect::<Vec<_>>());
+    }
+
     // Show distribution for duplicates
-    super::stats::run(args, &dupe_paths)?;
+    let stats_errors = super::stats::run(args, &dupe_paths)?;
+
+    if !errors.is_empty() || !report_errors.is_empty() || !stats_errors.is_empty() {
+        std::process::exit(crate::EXIT_FILE_ERRORS);
+    }
+    if !groups.is_empty() {
+        std::process::exit(crate::EXIT_DUPLICATES);
+    }
 
     Ok(())
 }
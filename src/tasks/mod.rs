@@ -1,2 +1,251 @@
 pub mod stats;
 pub mod duplicates;
+pub mod verify;
+pub mod report;
+pub mod benchmark;
+pub mod compare;
+
+use std::{
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use clap::ArgMatches;
+
+use chksum::{
+    cache::HashCache,
+    datetime::{datetime_to_string, now_to_dirname},
+    errors::ChksumError,
+    files::{CsvOptions, Overwrite},
+    hash::{HashEvent, HashOptions, HashType, IoStrategy, BUFFER_SIZE},
+    stats::Units,
+    walk::WalkOptions,
+};
+use time::OffsetDateTime;
+
+/// Returns hash types from `--algo`, or the single hash type flag (default BLAKE3),
+/// chunked if `--chunk-size` is set. Several hash types are calculated in a single read,
+/// the first is used where only one is needed (e.g. duplicates).
+pub fn hash_types(args: &ArgMatches) -> Vec<HashType> {
+    // only one of these can be set
+    let hash_type = [
+        ("sha256", HashType::Sha256),
+        ("md5", HashType::Md5),
+        ("sha512", HashType::Sha512),
+        ("sha1", HashType::Sha1),
+        ("sha3-256", HashType::Sha3_256),
+        ("blake2b", HashType::Blake2b),
+        ("xxh3", HashType::Xxh3_64),
+        ("xxh128", HashType::Xxh3_128),
+        ("crc32c", HashType::Crc32c),
+    ]
+    .into_iter()
+    .find_map(|(arg, hash_type)| args.get_flag(arg).then_some(hash_type))
+    .unwrap_or(HashType::Blake3);
+
+    let mut hash_types: Vec<HashType> = Vec::new();
+    for algo in args.get_many::<HashType>("algo").unwrap_or_default() {
+        if !hash_types.contains(algo) {
+            hash_types.push(algo.to_owned());
+        }
+    }
+    if hash_types.is_empty() {
+        hash_types.push(hash_type);
+    }
+    if let Some(chunk_size) = args.get_one::<u64>("chunk-size") {
+        hash_types = hash_types.into_iter().map(|h| h.chunked(*chunk_size)).collect();
+    }
+
+    hash_types
+}
+
+/// Returns options for walking source-dir and target-dir.
+pub fn walk_options(args: &ArgMatches) -> WalkOptions {
+    WalkOptions {
+        include_hidden: *args.get_one::<bool>("include-hidden").unwrap(),
+        follow_symlinks: *args.get_one::<bool>("follow-symlinks").unwrap(),
+        exclude_dir: args.get_many("exclude-dir").unwrap_or_default().cloned().collect(),
+        include_ext: args.get_many("include-ext").unwrap_or_default().cloned().collect(),
+        exclude_ext: args.get_many("exclude-ext").unwrap_or_default().cloned().collect(),
+        include: args.get_many("include").unwrap_or_default().cloned().collect(),
+        exclude: args.get_many("exclude").unwrap_or_default().cloned().collect(),
+        include_regex: args.get_many("include-regex").unwrap_or_default().cloned().collect(),
+        exclude_regex: args.get_many("exclude-regex").unwrap_or_default().cloned().collect(),
+        ignore_errors: *args.get_one::<bool>("ignore-path-errors").unwrap(),
+        use_ignore_files: *args.get_one::<bool>("use-ignore-files").unwrap(),
+        min_size: args.get_one::<u64>("min-size").copied(),
+        max_size: args.get_one::<u64>("max-size").copied(),
+        newer_than: args.get_one::<OffsetDateTime>("newer-than").copied(),
+        older_than: args.get_one::<OffsetDateTime>("older-than").copied(),
+        parallel: *args.get_one::<bool>("parallel-walk").unwrap(),
+    }
+}
+
+/// Opens the hash cache for directory `root` if `--cache` or `--cache-dir` is set.
+pub fn hash_cache(args: &ArgMatches, root: &Path) -> Option<HashCache> {
+    let cache_dir = args.get_one::<PathBuf>("cache-dir");
+    if !*args.get_one::<bool>("cache").unwrap() && cache_dir.is_none() {
+        return None;
+    }
+    let rehash = *args.get_one::<bool>("rehash").unwrap();
+
+    let cache = HashCache::open(root, cache_dir.map(|p| p.as_path()), rehash);
+    if cache.invalid {
+        eprintln!("(!) Ignoring invalid cache {}", cache.path().display());
+    }
+
+    Some(cache)
+}
+
+/// Saves the cache, printing a warning on failure, and prints hit/miss stats if `show_stats` is set.
+/// Corrupt files are listed unless `quiet` is set.
+pub fn finish_cache(cache: &mut HashCache, label: &str, show_stats: bool, quiet: bool) {
+    if show_stats && !quiet {
+        println!("[ {label} ] {}", cache.stats());
+    }
+    if !cache.corrupt.is_empty() && !quiet {
+        println!(
+            "[ {label} ] {} files corrupt (hash changed since cached, size and modification time did not):",
+            cache.corrupt.len()
+        );
+        for (i, path) in cache.corrupt.iter().enumerate() {
+            println!("  [ CORRUPT {:5} ] {}", i + 1, path.display());
+        }
+    }
+    if let Err(err) = cache.save() {
+        eprintln!("(!) Failed to write cache {}: {err}", cache.path().display());
+    }
+}
+
+/// Prints files that could not be hashed, one per line.
+pub fn print_errors(errors: &[&ChksumError]) {
+    print!("\n{} files could not be read", errors.len());
+    if errors.is_empty() {
+        println!();
+    } else {
+        println!(":");
+        for (i, err) in errors.iter().enumerate() {
+            println!("  [ ERROR   {:5} ] {err}", i + 1)
+        }
+    }
+}
+
+/// Prints a hashed file for `--verbose`, labelled with `dirtype`, e.g. `SOURCE`.
+/// Files that could not be hashed are listed by `print_errors` instead.
+pub fn print_hashed(event: &HashEvent, dirtype: &str) {
+    let Some(hashes) = event.hashes else {
+        return;
+    };
+    let (started, finished) = match event.cached {
+        true => ("(cached)".to_owned(), "(cached)".to_owned()),
+        false => {
+            let finished = OffsetDateTime::now_utc();
+            (datetime_to_string(&(finished - event.duration)), datetime_to_string(&finished))
+        }
+    };
+    // a single statement, so that output from several hashing threads is not interleaved
+    println!(
+        "─ {dirtype} ┬ FILE {started:23} {}\n         └ HASH {finished:23} {} {:12} bytes",
+        event.path.display(),
+        hashes.join(" "),
+        event.size.unwrap_or_default()
+    );
+}
+
+/// Returns true if `category` (e.g. "missing") is set via `--fail-on`.
+pub fn fail_on(args: &ArgMatches, category: &str) -> bool {
+    args.get_many::<String>("fail-on")
+        .into_iter()
+        .flatten()
+        .any(|c| c == category)
+}
+
+/// Prints the result of writing log file `path`.
+/// Only failures are printed if `quiet` is set, e.g. for `--format json`.
+pub fn print_written(result: std::io::Result<bool>, path: &Path, quiet: bool) {
+    match result {
+        Ok(true) if !quiet => println!("Wrote {}", path.display()),
+        Ok(false) if !quiet => println!("Kept existing {}", path.display()),
+        Err(err) => eprintln!("(!) Failed to write {}: {err}", path.display()),
+        _ => (),
+    }
+}
+
+/// Parses `--delimiter`: `comma`, `tab`, `semicolon`, or a single ASCII character.
+pub fn parse_delimiter(value: &str) -> Result<u8, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "comma" => Ok(b','),
+        "tab" | "\\t" => Ok(b'\t'),
+        "semicolon" => Ok(b';'),
+        _ => match value.as_bytes() {
+            [b'"'] => Err("Quote character can not be used as delimiter".to_owned()),
+            [byte] if byte.is_ascii() => Ok(*byte),
+            _ => Err(format!("Invalid delimiter '{value}'")),
+        },
+    }
+}
+
+/// Validates a glob pattern for `--include` and `--exclude`.
+pub fn parse_glob(value: &str) -> Result<String, String> {
+    chksum::walk::glob(value)
+        .map(|_| value.to_owned())
+        .map_err(|err| err.to_string())
+}
+
+/// Validates a regular expression for `--include-regex` and `--exclude-regex`.
+pub fn parse_regex(value: &str) -> Result<String, String> {
+    regex::Regex::new(value)
+        .map(|_| value.to_owned())
+        .map_err(|err| err.to_string())
+}
+
+/// Parses a size larger than zero, e.g. for `--chunk-size`, see `Units::parse_bytes`.
+pub fn parse_size(value: &str) -> Result<u64, String> {
+    match Units::parse_bytes(value)? {
+        0 => Err("Size must be larger than zero".to_owned()),
+        n => Ok(n),
+    }
+}
+
+/// Returns CSV options for logs from `--delimiter`, `--quote-all`, and `--bom`.
+pub fn csv_options(args: &ArgMatches) -> CsvOptions {
+    CsvOptions {
+        delimiter: *args.get_one::<u8>("delimiter").unwrap(), // clap default comma
+        quote_all: *args.get_one::<bool>("quote-all").unwrap(),
+        bom: *args.get_one::<bool>("bom").unwrap(),
+    }
+}
+
+/// Returns options for hashing individual files from `--parallel-hash-above`,
+/// `--io`, `--buffer-size`, and `--fadvise`.
+pub fn hash_options(args: &ArgMatches) -> HashOptions {
+    HashOptions {
+        parallel_above: args.get_one::<u64>("parallel-hash-above").copied(), // clap default 128MB
+        io: *args.get_one::<IoStrategy>("io").unwrap(), // clap default auto
+        buffer_size: args
+            .get_one::<u64>("buffer-size")
+            .map(|n| *n as usize)
+            .unwrap_or(BUFFER_SIZE),
+        fadvise: args.get_flag("fadvise"),
+        partial: None,
+    }
+}
+
+/// Returns the policy for existing files from `--overwrite` and `--no-clobber`.
+pub fn overwrite(args: &ArgMatches) -> Overwrite {
+    match (args.get_flag("overwrite"), args.get_flag("no-clobber")) {
+        (true, _) => Overwrite::Always,
+        (_, true) => Overwrite::Never,
+        _ => Overwrite::Prompt,
+    }
+}
+
+/// Returns log directory from `--log-dir` and `--run-dir`.
+/// The timestamped run directory is the same for all logs in a run.
+pub fn log_dir(args: &ArgMatches) -> std::io::Result<PathBuf> {
+    static RUN_DIR: OnceLock<String> = OnceLock::new();
+    let run_dir = args
+        .get_flag("run-dir")
+        .then(|| RUN_DIR.get_or_init(now_to_dirname).as_str());
+    chksum::files::log_dir(args.get_one::<PathBuf>("log-dir"), run_dir)
+}
@@ -0,0 +1,208 @@
+use std::path::{Path, PathBuf};
+
+use clap::ArgMatches;
+
+use chksum::{
+    compare::{ComparisonReport, HashedTree},
+    duplicates::DuplicateGroup,
+    errors::ChksumError,
+    files::writefile,
+    hash::{hash_types_to_string, HashType},
+    report::{Cell, HtmlReport},
+    stats::{extension_stats, Units},
+};
+
+use super::{overwrite, print_written};
+
+/// Writes an HTML report for a comparison to the path set via `--report`.
+/// Returns files that could not be read for file type statistics with `--continue-on-error`,
+/// e.g. deleted since they were hashed, which are also listed in the report.
+pub fn comparison(
+    args: &ArgMatches,
+    source: &HashedTree,
+    target: &HashedTree,
+    report: &ComparisonReport,
+    hash_types: &[HashType],
+    quiet: bool,
+) -> std::io::Result<Vec<ChksumError>> {
+    let Some(path) = args.get_one::<PathBuf>("report") else {
+        return Ok(Vec::new());
+    };
+    let case_sensitive = *args.get_one::<bool>("case-sensitive").unwrap();
+    let mut stat_errors: Vec<ChksumError> = Vec::new();
+    let continue_on_error = *args.get_one::<bool>("continue-on-error").unwrap();
+
+    // file stats require files on disk
+    let mut stats = Vec::new();
+    for (tree, side) in [(source, "source"), (target, "target")] {
+        if tree.root.is_some() {
+            let paths: Vec<PathBuf> = tree.hashes.values().map(|(p, _)| p.to_owned()).collect();
+            let side_stats = extension_stats(&paths, case_sensitive, continue_on_error.then_some(&mut stat_errors))?;
+            stats.push((format!("File types in {side}"), side_stats));
+        }
+    }
+
+    let name = |tree: &HashedTree, fallback: &str| {
+        tree.root
+            .as_ref()
+            .map(|p| p.display().to_string())
+            .unwrap_or(fallback.to_owned())
+    };
+    let (source_name, target_name) = (name(source, "source manifest"), name(target, "target manifest"));
+    let errors: Vec<&ChksumError> = source.errors
+        .iter()
+        .chain(target.errors.iter())
+        .chain(stat_errors.iter())
+        .collect();
+
+    let mut html = HtmlReport::new(&format!("chksum: {source_name} → {target_name}"));
+    html.totals("Summary", &[
+        ("Source", source_name.to_owned()),
+        ("Target", target_name.to_owned()),
+        ("Hash algorithm", hash_types_to_string(hash_types)),
+        ("Files in source", source.hashes.len().to_string()),
+        ("Files in target", target.hashes.len().to_string()),
+        ("Matched", report.matched.len().to_string()),
+        ("Matched (legacy digest)", report.legacy.len().to_string()),
+        ("Missing in target", report.missing.len().to_string()),
+        ("Moved/renamed in target", report.moved.len().to_string()),
+        ("Changed in target", report.changed.len().to_string()),
+        ("Corrupt in target", report.corrupt.len().to_string()),
+        ("New in target (not in source)", report.ignored.len().to_string()),
+        ("Could not be read", errors.len().to_string()),
+    ]);
+
+    // path, full path, size, modified
+    let files = |tree: &HashedTree, paths: &[PathBuf]| -> Vec<Vec<Cell>> {
+        paths
+            .iter()
+            .map(|path| {
+                let (size, modified) = tree.file_info(path);
+                vec![
+                    Cell::from(path.display().to_string()),
+                    Cell::from(tree.full_path(path).map(|p| p.display().to_string()).unwrap_or_default()),
+                    size.map(Cell::size).unwrap_or(Cell::from("-")),
+                    Cell::from(modified.unwrap_or("-".to_owned())),
+                ]
+            })
+            .collect()
+    };
+    // path, modified and size for both sides
+    let changed = |paths: &[PathBuf]| -> Vec<Vec<Cell>> {
+        paths
+            .iter()
+            .map(|path| {
+                let (source_size, source_modified) = source.file_info(path);
+                let (target_size, target_modified) = target.file_info(path);
+                vec![
+                    Cell::from(path.display().to_string()),
+                    Cell::from(source_modified.unwrap_or("-".to_owned())),
+                    source_size.map(Cell::size).unwrap_or(Cell::from("-")),
+                    Cell::from(target_modified.unwrap_or("-".to_owned())),
+                    target_size.map(Cell::size).unwrap_or(Cell::from("-")),
+                ]
+            })
+            .collect()
+    };
+    let changed_headers = ["Path", "Source modified", "Source size", "Target modified", "Target size"];
+
+    html.table("Missing in target", &["Path", "Source path", "Size", "Modified"], files(source, &report.missing));
+    html.table("Changed in target", &changed_headers, changed(&report.changed));
+    html.table(
+        "Corrupt in target (hash changed, size and modification time did not)",
+        &changed_headers,
+        changed(&report.corrupt),
+    );
+    html.table("New in target", &["Path", "Target path", "Size", "Modified"], files(target, &report.ignored));
+    html.table(
+        "Moved/renamed in target",
+        &["Source path", "Target path"],
+        report.moved
+            .iter()
+            .map(|(s, t)| vec![Cell::from(s.display().to_string()), Cell::from(t.display().to_string())])
+            .collect(),
+    );
+    html.table("Could not be read", &["Path", "Error"], error_rows(&errors));
+
+    for (heading, side_stats) in stats.iter() {
+        html.extension_stats(heading, side_stats);
+    }
+
+    print_written(writefile(&html.to_html(), path, overwrite(args)), path, quiet);
+    Ok(stat_errors)
+}
+
+/// Writes an HTML report for a duplicates scan to the path set via `--report`.
+/// Returns files that could not be read as for `comparison`.
+pub fn duplicates(
+    args: &ArgMatches,
+    file_count: usize,
+    groups: &[DuplicateGroup],
+    hash_type: &HashType,
+    errors: &[&ChksumError],
+) -> std::io::Result<Vec<ChksumError>> {
+    let Some(path) = args.get_one::<PathBuf>("report") else {
+        return Ok(Vec::new());
+    };
+    let dir = args.get_one::<PathBuf>("source-dir").map(|p| p.as_path()).unwrap_or(Path::new(""));
+    let case_sensitive = *args.get_one::<bool>("case-sensitive").unwrap();
+    let mut stat_errors: Vec<ChksumError> = Vec::new();
+    let continue_on_error = *args.get_one::<bool>("continue-on-error").unwrap();
+
+    // wasted space: all copies but one
+    let sizes = groups
+        .iter()
+        .map(|group| group.paths.first().and_then(|p| p.metadata().ok()).map(|m| m.len()).unwrap_or_default())
+        .collect::<Vec<_>>();
+    let wasted: u64 = groups
+        .iter()
+        .zip(sizes.iter())
+        .map(|(group, size)| size * (group.paths.len() as u64 - 1))
+        .sum();
+    let dupe_paths: Vec<PathBuf> = groups.iter().flat_map(|g| g.paths.to_owned()).collect();
+    let stats = extension_stats(&dupe_paths, case_sensitive, continue_on_error.then_some(&mut stat_errors))?;
+    let errors: Vec<&ChksumError> = errors.iter().copied().chain(stat_errors.iter()).collect();
+
+    let mut html = HtmlReport::new(&format!("chksum: duplicates in {}", dir.display()));
+    html.totals("Summary", &[
+        ("Directory", dir.display().to_string()),
+        ("Hash algorithm", hash_type.to_string()),
+        ("Files, total", file_count.to_string()),
+        ("Duplicate files, total", dupe_paths.len().to_string()),
+        ("...with unique hashes", groups.len().to_string()),
+        ("Wasted space", Units::from(wasted).to_string()),
+        ("Could not be read", errors.len().to_string()),
+    ]);
+    html.table(
+        "Duplicate groups",
+        &["#", "Files", "Size", "Wasted", "Hash", "Paths"],
+        groups
+            .iter()
+            .zip(sizes.iter())
+            .enumerate()
+            .map(|(i, (group, size))| vec![
+                Cell::from((i + 1).to_string()),
+                Cell::from(group.paths.len().to_string()),
+                Cell::size(*size),
+                Cell::size(size * (group.paths.len() as u64 - 1)),
+                Cell::from(group.hash.to_owned()),
+                Cell::from(group.paths.iter().map(|p| p.display().to_string()).collect::<Vec<_>>().join("\n")),
+            ])
+            .collect(),
+    );
+    html.table("Could not be read", &["Path", "Error"], error_rows(&errors));
+    html.extension_stats("File types of duplicates", &stats);
+
+    print_written(writefile(&html.to_html(), path, overwrite(args)), path, false);
+    Ok(stat_errors)
+}
+
+fn error_rows(errors: &[&ChksumError]) -> Vec<Vec<Cell>> {
+    errors
+        .iter()
+        .map(|err| vec![
+            Cell::from(err.path().map(|p| p.display().to_string()).unwrap_or_default()),
+            Cell::from(err.to_string()),
+        ])
+        .collect()
+}
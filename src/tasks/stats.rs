@@ -1,10 +1,16 @@
-use std::{collections::HashMap, path::PathBuf};
+use std::path::PathBuf;
 
 use clap::ArgMatches;
 
-use crate::files::file_stats;
+use chksum::{
+    errors::ChksumError,
+    stats::{extension_stats, Units},
+};
 
-pub fn run(args: &ArgMatches, paths: &[PathBuf]) -> std::io::Result<()> {
+/// Prints size and file count per file extension for `paths`.
+/// Returns files that could not be read with `--continue-on-error`, e.g. deleted since
+/// they were found, which are skipped and listed. Otherwise these abort.
+pub fn run(args: &ArgMatches, paths: &[PathBuf]) -> std::io::Result<Vec<ChksumError>> {
     let fileext_case_sensitive = *args.get_one::<bool>("case-sensitive").unwrap();
     let filestats_sort_count = *args.get_one::<bool>("stats-sort-count").unwrap();
     let filestats_sort_size = *args.get_one::<bool>("stats-sort-size").unwrap();
@@ -14,32 +20,16 @@ pub fn run(args: &ArgMatches, paths: &[PathBuf]) -> std::io::Result<()> {
     let mut threshold = *args.get_one::<f64>("threshold").unwrap();
     if threshold > 100. {
         let msg = format!("{threshold} is not a valid value for 'threshold'. Must be a between 0.0 - 100.0 (%)");
-        return Err(std::io::Error::new(std::io::ErrorKind::Other, msg))
+        return Err(std::io::Error::other(msg))
     }
 
-    let mut stats: HashMap<String, (u64, usize)> = HashMap::new();
-    let mut total_size = 0;
-
-    for path in paths.iter() {
-        let (mut ext, size) = file_stats(path)
-            .map(|(e, s, _created, _modified)| (e.unwrap_or("< no ext >".to_owned()), s))?;
-
-        total_size += size;
-
-        if !fileext_case_sensitive {
-            ext = ext.to_lowercase();
-        }
-
-        stats.entry(ext)
-            .and_modify(|v| {
-                v.0 += size;
-                v.1 += 1;
-            })
-            .or_insert((size, 1));
-    }
+    let continue_on_error = *args.get_one::<bool>("continue-on-error").unwrap();
+    let mut errors: Vec<ChksumError> = Vec::new();
+    let stats = extension_stats(paths, fileext_case_sensitive, continue_on_error.then_some(&mut errors))?;
+    let total_size: u64 = stats.iter().map(|s| s.size).sum();
 
     let mut stats_vec = stats.iter()
-        .map(|(ext, (size, count))| (ext, size, count))
+        .map(|s| (&s.extension, &s.size, &s.count))
         .collect::<Vec<_>>();
 
     match (filestats_sort_alpha, filestats_sort_size, filestats_sort_count) {
@@ -63,7 +53,7 @@ pub fn run(args: &ArgMatches, paths: &[PathBuf]) -> std::io::Result<()> {
 
         // 100 blocks = total size, perhaps lower for term width
         let blocks_full = relative_size.floor();
-        let blocks_rem = relative_size - blocks_full as f64;
+        let blocks_rem = relative_size - blocks_full;
         // only show file types above a certain total size, defaults to 1%
         if relative_size > threshold {
             print!("{ext:>21} {count:<10} {:>10} [{:>6}%] ",
@@ -98,7 +88,7 @@ pub fn run(args: &ArgMatches, paths: &[PathBuf]) -> std::io::Result<()> {
         );
 
         let blocks_full = other_relative_size.floor();
-        let blocks_rem = other_relative_size - blocks_full as f64;
+        let blocks_rem = other_relative_size - blocks_full;
         // checking single ext not needed here
         let block_partial = match blocks_rem {
             0.0..0.33 => "░",
@@ -110,7 +100,7 @@ pub fn run(args: &ArgMatches, paths: &[PathBuf]) -> std::io::Result<()> {
         println!("{block_string:<100}");
     }
     println!("---");
-    println!("Total                 {:<13} {:>7}", paths.len(), Units::from(total_size).to_string());
+    println!("Total                 {:<13} {:>7}", paths.len() - errors.len(), Units::from(total_size).to_string());
     if args.contains_id("include-ext") {
         println!("\n'include-ext' set: showing total relative size for all included file types.")
     } else {
@@ -119,38 +109,9 @@ pub fn run(args: &ArgMatches, paths: &[PathBuf]) -> std::io::Result<()> {
         );
     }
 
-    Ok(())
-}
-
-pub enum Units {
-    Bytes(u64),
-    Kilo(f64),
-    Mega(f64),
-    Giga(f64),
-    Tera(f64),
-}
-
-impl From<u64> for Units {
-    fn from(value: u64) -> Self {
-        match value as f64 {
-            _z @ ..1e3 => Self::Bytes(value),
-            z @ 1e3..1e6 => Self::Kilo(z / 1e3),
-            z @ 1e6..1e9 => Self::Mega(z / 1e6),
-            z @ 1e9..1e12 => Self::Giga(z / 1e9),
-            z @ 1e12..1e15 => Self::Tera(z / 1e12),
-            z => Self::Tera(z / 1e12),
-        }
+    if !errors.is_empty() {
+        super::print_errors(&errors.iter().collect::<Vec<_>>());
     }
-}
 
-impl std::fmt::Display for Units {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Units::Bytes(n) => write!(f, "{n} bytes"),
-            Units::Kilo(fl) => write!(f, "{fl:.2}KB", ),
-            Units::Mega(fl) => write!(f, "{fl:.2}MB", ),
-            Units::Giga(fl) => write!(f, "{fl:.2}GB", ),
-            Units::Tera(fl) => write!(f, "{fl:.2}TB", ),
-        }
-    }
-}
\ No newline at end of file
+    Ok(errors)
+}
@@ -0,0 +1,135 @@
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use clap::ArgMatches;
+
+use chksum::{
+    datetime::now_to_string,
+    errors::{errors_to_log, ChksumError},
+    files::{filename_to_string, writefile, LogLevel},
+    hash::{hash_headers, hash_types_to_string, HashContext, HashEvent, HashType, OnHashed},
+    manifest::Manifest,
+    verify::{verify, VerifyStatus},
+};
+
+use super::{csv_options, fail_on, finish_cache, hash_cache, print_errors, print_hashed, print_written};
+
+/// Re-hash all paths listed in a previously written checksum log
+/// or coreutils manifest and compare with the logged hashes.
+///
+/// `hash_type` is only used for coreutils manifests if the hash algorithm
+/// can not be inferred from the manifest itself. If the manifest lists
+/// several hash types, all are verified in a single read per file.
+/// Paths relative to the hashed directory are resolved against `source-dir` if set
+/// (e.g. a copy or a moved directory), otherwise logged paths are used as is.
+pub fn run(args: &ArgMatches, manifest_path: &Path, hash_type: &HashType) -> std::io::Result<()> {
+    let verbose = *args.get_one::<bool>("verbose").unwrap();
+    let log_level = LogLevel::from(*args.get_one::<bool>("log").unwrap());
+    let overwrite = super::overwrite(args);
+    let base_dir = args.get_one::<PathBuf>("source-dir");
+    let continue_on_error = *args.get_one::<bool>("continue-on-error").unwrap();
+
+    print!("[ VERIFY | {} ] Reading manifest...", manifest_path.display());
+    std::io::stdout().flush()?;
+    let manifest = Manifest::from_path(manifest_path, hash_type)?;
+    println!(
+        " Done ({} files, {}, {})",
+        manifest.entries.len(),
+        manifest.format,
+        hash_types_to_string(&manifest.hash_types)
+    );
+
+    println!("[ VERIFY | {} ] Compiling hashes...", manifest_path.display());
+    let mut cache = hash_cache(args, base_dir.map(|p| p.as_path()).unwrap_or(Path::new(".")));
+    let mut errors: Vec<ChksumError> = Vec::new();
+    let on_hashed = |event: &HashEvent| print_hashed(event, "VERIFY");
+    let results = verify(
+        &manifest,
+        base_dir.map(|p| p.as_path()),
+        &super::hash_options(args),
+        HashContext {
+            cache: cache.as_mut(),
+            errors: continue_on_error.then_some(&mut errors),
+            on_hashed: verbose.then_some(&on_hashed as OnHashed),
+        },
+    )?;
+    println!("Done ({} files)\n", results.iter().filter(|r| r.actual.is_some()).count());
+
+    if let Some(cache) = cache.as_mut() {
+        finish_cache(cache, &format!("VERIFY | {}", manifest_path.display()), *args.get_one::<bool>("cache-stats").unwrap(), false);
+    }
+
+    let mut log_verified = vec![[
+        vec!["FILENAME".to_owned(), "PATH".to_owned()],
+        hash_headers(&manifest.hash_types, "EXPECTED"),
+        hash_headers(&manifest.hash_types, "ACTUAL"),
+        vec!["STATUS".to_owned(), "DATETIME".to_owned()],
+    ].concat()];
+    // empty columns for missing files
+    let no_hashes = vec![String::new(); manifest.hash_types.len()];
+
+    for (i, result) in results.iter().enumerate() {
+        println!("  [ {:7} {:5} ] {}", result.status.to_string(), i + 1, result.path.display());
+
+        if log_level == LogLevel::Normal {
+            log_verified.push([
+                vec![
+                    filename_to_string(&result.path).unwrap_or("FILENAME ERROR".to_owned()),
+                    result.path.display().to_string(),
+                ],
+                result.expected.to_owned(),
+                result.actual.to_owned().unwrap_or(no_hashes.to_owned()),
+                vec![result.status.to_string(), now_to_string()],
+            ].concat());
+        }
+    }
+
+    let count = |status: VerifyStatus| results.iter().filter(|r| r.status == status).count();
+    let corrupt = count(VerifyStatus::Corrupt);
+
+    let errors: Vec<&ChksumError> = errors.iter().collect();
+    if continue_on_error {
+        print_errors(&errors);
+    }
+
+    println!("\nSummary");
+    println!("{}/{} files OK", count(VerifyStatus::Ok), manifest.entries.len());
+    if count(VerifyStatus::OkLegacy) > 0 {
+        println!("{:4} files OK (legacy digest, logged by chksum up to 2024-11-06)", count(VerifyStatus::OkLegacy));
+    }
+    println!("{:4} files FAILED", count(VerifyStatus::Failed));
+    println!("{:4} files MISSING", count(VerifyStatus::Missing));
+    println!("{:4} files CORRUPT (hash changed, size and modification time did not)", corrupt);
+    if continue_on_error {
+        println!("{:4} files ERROR (could not be read)", errors.len());
+    }
+
+    if log_level == LogLevel::Normal {
+        let log_dir = super::log_dir(args)?;
+        let csv = csv_options(args);
+
+        let log_path = log_dir.join(Path::new("verified.csv"));
+        print_written(writefile(&csv.to_csv(&log_verified)?, &log_path, overwrite), &log_path, false);
+
+        if !errors.is_empty() {
+            let log_errors_path = log_dir.join(Path::new("errors.csv"));
+            print_written(writefile(&csv.to_csv(&errors_to_log(&errors))?, &log_errors_path, overwrite), &log_errors_path, false);
+        }
+    }
+
+    if corrupt > 0 {
+        std::process::exit(crate::EXIT_CORRUPT);
+    }
+    if !errors.is_empty() {
+        std::process::exit(crate::EXIT_FILE_ERRORS);
+    }
+    if (count(VerifyStatus::Failed) > 0 && fail_on(args, "changed"))
+        || (count(VerifyStatus::Missing) > 0 && fail_on(args, "missing"))
+    {
+        std::process::exit(crate::EXIT_DIFFERENCES);
+    }
+
+    Ok(())
+}
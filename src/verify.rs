@@ -0,0 +1,241 @@
+use std::{
+    collections::HashSet,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    errors::ChksumError,
+    files::{file_info, is_corrupt},
+    hash::{hash_files, is_legacy_match, HashContext, HashOptions},
+    manifest::Manifest,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VerifyStatus {
+    /// Hash matches manifest.
+    Ok,
+    /// Hash matches manifest as encoded by chksum up to 2024-11-06, see `legacy_digest`.
+    OkLegacy,
+    /// Hash does not match manifest.
+    Failed,
+    /// Path listed in manifest no longer exists.
+    Missing,
+    /// Hash does not match manifest, but logged size and modification time do.
+    /// Suggests silent corruption rather than an edit.
+    Corrupt,
+    /// File could not be read.
+    Error,
+}
+
+impl fmt::Display for VerifyStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerifyStatus::Ok => write!(f, "OK"),
+            VerifyStatus::OkLegacy => write!(f, "OK (legacy digest)"),
+            VerifyStatus::Failed => write!(f, "FAILED"),
+            VerifyStatus::Missing => write!(f, "MISSING"),
+            VerifyStatus::Corrupt => write!(f, "CORRUPT"),
+            VerifyStatus::Error => write!(f, "ERROR"),
+        }
+    }
+}
+
+/// Verification result for a single manifest entry.
+#[derive(Debug, Clone)]
+pub struct VerifyResult {
+    /// Relative path joined with the base directory if set, otherwise the path as listed in the manifest.
+    pub path: PathBuf,
+    /// Hashes as listed in the manifest.
+    pub expected: Vec<String>,
+    /// Hashes for the file on disk. `None` if missing or unreadable.
+    pub actual: Option<Vec<String>>,
+    pub status: VerifyStatus,
+}
+
+/// Re-hash all paths listed in a manifest and compare with the listed hashes.
+/// If `base_dir` is set, the relative path for each entry is resolved against it
+/// (e.g. a copy of the hashed directory), otherwise the path as listed is used.
+/// If the manifest lists several hash types, all are verified in a single read per file.
+///
+/// Results are in manifest order. See `HashContext` for `context`.
+pub fn verify(
+    manifest: &Manifest,
+    base_dir: Option<&Path>,
+    hash_options: &HashOptions,
+    context: HashContext,
+) -> Result<Vec<VerifyResult>, ChksumError> {
+    let HashContext { cache, mut errors, on_hashed } = context;
+    let full_paths: Vec<PathBuf> = manifest
+        .entries
+        .iter()
+        .map(|entry| match base_dir {
+            Some(dir) => dir.join(&entry.relative_path),
+            None => entry.path.to_owned(),
+        })
+        .collect();
+
+    // Hashing files that no longer exist raises an error
+    let existing_paths: Vec<PathBuf> = full_paths
+        .iter()
+        .filter(|path| path.exists())
+        .cloned()
+        .collect();
+
+    let hashes = hash_files(
+        &existing_paths,
+        &manifest.hash_types,
+        hash_options,
+        None,
+        HashContext { cache, errors: errors.as_deref_mut(), on_hashed },
+    )?;
+    let errored: HashSet<&Path> = errors
+        .iter()
+        .flat_map(|errors| errors.iter())
+        .filter_map(|err| err.path())
+        .collect();
+
+    let results = manifest
+        .entries
+        .iter()
+        .zip(full_paths)
+        .map(|(entry, full_path)| {
+            let actual = hashes.get(&full_path).map(|(_, hashes)| hashes.to_owned());
+            let status = match &actual {
+                Some(hashes) if hashes == &entry.hashes => VerifyStatus::Ok,
+                Some(hashes) if is_legacy_match(&entry.hashes, hashes) => VerifyStatus::OkLegacy,
+                Some(_) if is_corrupt(&(entry.size, entry.modified.to_owned()), &file_info(&full_path)) => {
+                    VerifyStatus::Corrupt
+                }
+                Some(_) => VerifyStatus::Failed,
+                None if errored.contains(full_path.as_path()) => VerifyStatus::Error,
+                None => VerifyStatus::Missing,
+            };
+            VerifyResult {
+                path: full_path,
+                expected: entry.hashes.to_owned(),
+                actual,
+                status,
+            }
+        })
+        .collect();
+
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        files::{checksum_header, checksum_row, CsvOptions},
+        hash::HashType,
+    };
+
+    use super::*;
+
+    #[test]
+    fn verify_moved_tree() {
+        let root = std::env::temp_dir().join(format!("chksum_verify_test_{}", std::process::id()));
+        let source = root.join("src");
+        std::fs::create_dir_all(source.join("dir")).unwrap();
+        std::fs::write(source.join("a.txt"), "a").unwrap();
+        std::fs::write(source.join("dir/b.txt"), "b").unwrap();
+
+        let hash_types = [HashType::Sha256];
+        let paths = [source.join("a.txt"), source.join("dir/b.txt")];
+        let hashes = hash_files(&paths, &hash_types, &HashOptions::default(), Some(&source), HashContext::default()).unwrap();
+        let log: String = hashes
+            .iter()
+            .map(|(relative_path, (path, hashes))| format!("{},{},{},{}\n", relative_path.file_name().unwrap().to_string_lossy(), path.display(), relative_path.display(), hashes[0]))
+            .collect();
+        let log_path = root.join("checksums.csv");
+        std::fs::write(&log_path, format!("FILENAME,SOURCEPATH,RELATIVEPATH,SHA256\n{log}")).unwrap();
+        let manifest = Manifest::from_path(&log_path, &HashType::Blake3).unwrap();
+
+        let moved = root.join("moved");
+        std::fs::rename(&source, &moved).unwrap();
+        std::fs::write(moved.join("dir/b.txt"), "c").unwrap();
+        let mut results = verify(&manifest, Some(&moved), &HashOptions::default(), HashContext::default()).unwrap();
+        results.sort_by(|a, b| a.path.cmp(&b.path));
+        let statuses: Vec<(PathBuf, VerifyStatus)> = results.into_iter().map(|r| (r.path, r.status)).collect();
+        assert_eq!(statuses, [(moved.join("a.txt"), VerifyStatus::Ok), (moved.join("dir/b.txt"), VerifyStatus::Failed)]);
+
+        // logged paths no longer exist
+        let results = verify(&manifest, None, &HashOptions::default(), HashContext::default()).unwrap();
+        assert!(results.iter().all(|r| r.status == VerifyStatus::Missing));
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn verify_legacy_log() {
+        let root = std::env::temp_dir().join(format!("chksum_verify_legacy_test_{}", std::process::id()));
+        std::fs::create_dir_all(root.join("d")).unwrap();
+        std::fs::write(root.join("d/f1.txt"), "file 1\n").unwrap();
+        std::fs::write(root.join("d/f2.txt"), "file 2 changed\n").unwrap();
+
+        // written by chksum 0.5.0 (2024-11-06): 'chksum -s d --log --sha256'
+        let log = "FILENAME\tSOURCEPATH\tSHA256\tDATETIME\n\
+            f2.txt\td/f2.txt\t0b7e1391e807367614c568fd10a4a563cf0674268529f3fe768ed7042624c006\t2026-10-17 01:57:04.535\n\
+            f1.txt\td/f1.txt\t5f5d786c7877d87af911ade1b2ae7cb593c17674282091f3ace31efd9e951360\t2026-10-17 01:57:04.535\n";
+        let log_path = root.join("checksums.csv");
+        std::fs::write(&log_path, log).unwrap();
+        let manifest = Manifest::from_path(&log_path, &HashType::Blake3).unwrap();
+
+        let results = verify(&manifest, Some(&root.join("d")), &HashOptions::default(), HashContext::default()).unwrap();
+        let statuses: Vec<VerifyStatus> = results.iter().map(|r| r.status).collect();
+        assert_eq!(statuses, [VerifyStatus::Failed, VerifyStatus::OkLegacy]);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn verify_statuses() {
+        let root = std::env::temp_dir().join(format!("chksum_verify_statuses_test_{}", std::process::id()));
+        std::fs::create_dir_all(root.join("dir.txt")).unwrap();
+        for name in ["ok.txt", "failed.txt", "corrupt.txt", "missing.txt"] {
+            std::fs::write(root.join(name), name).unwrap();
+        }
+
+        let hash_types = [HashType::Sha256];
+        let names = ["ok.txt", "failed.txt", "corrupt.txt", "missing.txt", "dir.txt"];
+        let rows: Vec<Vec<String>> = names
+            .iter()
+            .map(|name| {
+                let path = root.join(name);
+                let (size, modified) = file_info(&path);
+                let (hash, modified) = match *name {
+                    "ok.txt" | "missing.txt" => {
+                        let hashes = hash_files(&[path.to_owned()], &hash_types, &HashOptions::default(), None, HashContext::default()).unwrap();
+                        (hashes[&path].1[0].to_owned(), modified)
+                    }
+                    // edited after it was logged
+                    "failed.txt" => ("00".repeat(32), Some("2000-01-01 00:00:00.000".to_owned())),
+                    _ => ("00".repeat(32), modified),
+                };
+                checksum_row(&path, Path::new(name), (size, modified), &[hash])
+            })
+            .collect();
+        let log = CsvOptions::default()
+            .to_csv(&[vec![checksum_header("SOURCEPATH", &hash_types)], rows].concat())
+            .unwrap();
+        let log_path = root.join("checksums.csv");
+        std::fs::write(&log_path, log).unwrap();
+        let manifest = Manifest::from_path(&log_path, &HashType::Blake3).unwrap();
+        std::fs::remove_file(root.join("missing.txt")).unwrap();
+
+        let mut errors = Vec::new();
+        let context = HashContext { errors: Some(&mut errors), ..HashContext::default() };
+        let results = verify(&manifest, Some(&root), &HashOptions::default(), context).unwrap();
+        let statuses: Vec<VerifyStatus> = results.iter().map(|r| r.status).collect();
+        assert_eq!(statuses, [
+            VerifyStatus::Ok,
+            VerifyStatus::Failed,
+            VerifyStatus::Corrupt,
+            VerifyStatus::Missing,
+            VerifyStatus::Error,
+        ]);
+        assert_eq!(errors.len(), 1);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
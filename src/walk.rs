@@ -0,0 +1,492 @@
+use std::{
+    collections::HashMap,
+    ffi::OsStr,
+    fs::Metadata,
+    path::{Path, PathBuf},
+    sync::mpsc::channel,
+};
+
+use globset::{Glob, GlobBuilder, GlobSet, GlobSetBuilder};
+use ignore::{gitignore::{Gitignore, GitignoreBuilder}, Match, WalkBuilder, WalkState};
+use regex::Regex;
+use time::OffsetDateTime;
+use walkdir::{DirEntry, WalkDir};
+
+/// Options for compiling file paths in a directory tree.
+#[derive(Debug, Clone, Default)]
+pub struct WalkOptions {
+    /// Include hidden files (filename starts with '.'). Currently only works for unix/linux.
+    pub include_hidden: bool,
+    /// Follow symlinks. Symlinks will otherwise be ignored.
+    pub follow_symlinks: bool,
+    /// Exclude any directory with this name (not path).
+    pub exclude_dir: Vec<String>,
+    /// File extensions to consider. Ignores all other files.
+    pub include_ext: Vec<String>,
+    /// File extensions to exclude.
+    pub exclude_ext: Vec<String>,
+    /// Glob patterns for files to consider, e.g. `**/*.jpg`. Ignores all other files.
+    /// See `PathFilter` for how patterns are matched.
+    pub include: Vec<String>,
+    /// Glob patterns for files and directories to exclude, e.g. `**/cache/**`, `*.tmp`, `Thumbs.db`.
+    pub exclude: Vec<String>,
+    /// Regular expressions for files to consider. Ignores all other files.
+    pub include_regex: Vec<String>,
+    /// Regular expressions for files and directories to exclude.
+    pub exclude_regex: Vec<String>,
+    /// Skip directory entries that raise errors instead of halting.
+    pub ignore_errors: bool,
+    /// Skip files and directories matched by `.gitignore`, `.ignore`, or `.chksumignore`
+    /// files anywhere in the walked directory. See `IgnoreFiles`.
+    pub use_ignore_files: bool,
+    /// Minimum file size in bytes, inclusive.
+    pub min_size: Option<u64>,
+    /// Maximum file size in bytes, inclusive.
+    pub max_size: Option<u64>,
+    /// Only files modified after this time.
+    pub newer_than: Option<OffsetDateTime>,
+    /// Only files modified before this time.
+    pub older_than: Option<OffsetDateTime>,
+    /// Walk directories in several threads, see `walk`.
+    pub parallel: bool,
+}
+
+/// Passed to `Walkdir::filter`. Returns `true` if `DirEntry`:
+/// - is/is not hidden, (`include_hidden` - currently only Unix/Linux)
+/// - does not contains a component/dir name that equeals `exclude_dir`
+/// - has a file extension specified in `include_ext`
+/// - does no have a file extension specified in `exclude_ext`
+/// - is within `min_size`/`max_size` and `newer_than`/`older_than`
+///   (files that can not be stat'ed are included, to be reported when hashed)
+pub fn include(direntry: &DirEntry, options: &WalkOptions) -> bool {
+    include_path(
+        direntry.path(),
+        direntry.file_type().is_dir(),
+        direntry.path_is_symlink(),
+        || direntry.metadata().ok(),
+        options,
+    )
+}
+
+/// Same as `include`, for walkers other than `WalkDir`. `is_dir` is taken from the walker's
+/// directory entry (the followed type for symlinks), so that no path needs to be stat'ed.
+/// `metadata` is only called if a size or modification time filter is set.
+fn include_path(
+    path: &Path,
+    is_dir: bool,
+    path_is_symlink: bool,
+    metadata: impl FnOnce() -> Option<Metadata>,
+    options: &WalkOptions,
+) -> bool {
+
+    // WalkDir should have already followed/resolved
+    // paths if follow_links is set, but when not set
+    // symlinks need to be ignored
+    if path_is_symlink && !options.follow_symlinks {
+        return false;
+    }
+
+    if is_dir {
+        return false;
+    }
+
+    if contains_dir(path, &options.exclude_dir) {
+        return false;
+    }
+
+    let is_hidden = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .map(|n| n.starts_with(".")) // unix/linux only, windows has attributes that need checking
+        .unwrap_or(false);
+    if !options.include_hidden && is_hidden {
+        return false;
+    }
+
+    // symlinks are followed or excluded above
+    let filter_meta = options.min_size.is_some()
+        || options.max_size.is_some()
+        || options.newer_than.is_some()
+        || options.older_than.is_some();
+    if let Some(meta) = filter_meta.then(metadata).flatten() {
+        let size = meta.len();
+        if options.min_size.is_some_and(|min| size < min) || options.max_size.is_some_and(|max| size > max) {
+            return false;
+        }
+        if options.newer_than.is_some() || options.older_than.is_some() {
+            let modified = meta.modified().ok().map(OffsetDateTime::from);
+            if let Some(modified) = modified {
+                if options.newer_than.is_some_and(|t| modified <= t) || options.older_than.is_some_and(|t| modified >= t) {
+                    return false;
+                }
+            }
+        }
+    }
+
+    if let Some(ext) = path.extension() {
+        let ext_str = ext.to_string_lossy().to_ascii_lowercase();
+        if !options.include_ext.is_empty() {
+            return options.include_ext.contains(&ext_str);
+        }
+
+        if !options.exclude_ext.is_empty() {
+            return !options.exclude_ext.contains(&ext_str);
+        }
+    } else {
+        // Check to ignore files without extension whenever
+        // include extentions are specified
+        if !options.include_ext.is_empty() {
+            return false;
+        }
+    }
+
+    true
+}
+
+fn contains_dir(path: &Path, dirs: &[String]) -> bool {
+    for dir in dirs.iter() {
+        if path
+            .components()
+            .any(|c| c == std::path::Component::Normal(OsStr::new(dir)))
+        {
+            return true;
+        }
+    }
+    false
+}
+
+/// Compiled `include`, `exclude`, `include_regex`, and `exclude_regex` patterns from `WalkOptions`.
+///
+/// Patterns are matched against the path relative to the walked directory, with `/` as separator.
+/// As for `.gitignore`, glob patterns without `/` match names at any depth (`*.tmp` is `**/*.tmp`),
+/// and a leading `/` anchors the pattern to the walked directory. `*` does not match `/`.
+///
+/// Directories matching an exclude pattern are pruned, i.e. never traversed.
+/// Glob patterns ending in `/**` also exclude the directory itself.
+/// As for `.gitignore`, a trailing `/` matches directories only, i.e. `cache/` is `**/cache/**`.
+/// For regular expressions directories are matched with a trailing `/`, e.g. `cache/$`.
+#[derive(Debug, Clone)]
+pub struct PathFilter {
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+    exclude_dir: GlobSet,
+    include_regex: Vec<Regex>,
+    exclude_regex: Vec<Regex>,
+}
+
+impl PathFilter {
+    pub fn new(options: &WalkOptions) -> std::io::Result<Self> {
+        let globset = |patterns: &[String], dirs: bool| -> std::io::Result<GlobSet> {
+            let mut builder = GlobSetBuilder::new();
+            for pattern in patterns.iter().map(|p| dir_pattern(p)) {
+                builder.add(glob(&pattern)?);
+                if let Some(dir) = pattern.strip_suffix("/**").filter(|_| dirs) {
+                    builder.add(glob(dir)?);
+                }
+            }
+            builder.build().map_err(std::io::Error::other)
+        };
+        let regexes = |patterns: &[String]| -> std::io::Result<Vec<Regex>> {
+            patterns.iter()
+                .map(|p| Regex::new(p).map_err(std::io::Error::other))
+                .collect()
+        };
+
+        Ok(Self {
+            include: match options.include.is_empty() {
+                true => None,
+                false => Some(globset(&options.include, false)?),
+            },
+            exclude: globset(&options.exclude, false)?,
+            exclude_dir: globset(&options.exclude, true)?,
+            include_regex: regexes(&options.include_regex)?,
+            exclude_regex: regexes(&options.exclude_regex)?,
+        })
+    }
+
+    /// Returns `true` if directory `relative_path` and its content should be skipped.
+    pub fn is_excluded_dir(&self, relative_path: &Path) -> bool {
+        let path = format!("{}/", slash_path(relative_path));
+        self.exclude_dir.is_match(relative_path)
+            || self.exclude_regex.iter().any(|r| r.is_match(&path))
+    }
+
+    /// Returns `true` if file `relative_path` should be included.
+    pub fn is_included_file(&self, relative_path: &Path) -> bool {
+        let path = slash_path(relative_path);
+        self.include.as_ref().is_none_or(|set| set.is_match(relative_path))
+            && !self.exclude.is_match(relative_path)
+            && (self.include_regex.is_empty() || self.include_regex.iter().any(|r| r.is_match(&path)))
+            && !self.exclude_regex.iter().any(|r| r.is_match(&path))
+    }
+}
+
+/// Compiles a glob pattern, see `PathFilter`.
+pub fn glob(pattern: &str) -> std::io::Result<Glob> {
+    let pattern = match pattern.strip_prefix('/') {
+        Some(anchored) => anchored.to_owned(),
+        None if pattern.contains('/') => pattern.to_owned(),
+        None => format!("**/{pattern}"),
+    };
+    GlobBuilder::new(&pattern)
+        .literal_separator(true)
+        .build()
+        .map_err(std::io::Error::other)
+}
+
+/// Expands a pattern with a trailing `/` to match everything in the directory,
+/// e.g. `cache/` to `**/cache/**` and `/a/cache/` to `/a/cache/**`.
+/// The trailing `/` does not anchor the pattern.
+fn dir_pattern(pattern: &str) -> String {
+    match pattern.strip_suffix('/').map(|p| p.trim_end_matches('/')) {
+        Some(dir) if !dir.is_empty() && !dir.contains('/') => format!("**/{dir}/**"),
+        Some(dir) if !dir.is_empty() => format!("{dir}/**"),
+        _ => pattern.to_owned(),
+    }
+}
+
+/// Returns path as string with `/` as separator on all platforms.
+fn slash_path(path: &Path) -> String {
+    path.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Ignore files, in increasing order of precedence.
+pub const IGNORE_FILES: [&str; 3] = [".gitignore", ".ignore", ".chksumignore"];
+
+/// Rules from `.gitignore`, `.ignore`, and `.chksumignore` files (gitignore syntax),
+/// loaded per directory as the walk reaches it.
+///
+/// As for git, rules in a directory apply to everything below it, and rules closer
+/// to a path take precedence, as does a later file in `IGNORE_FILES` within the same
+/// directory. Ignore files outside the walked directory, `.git/info/exclude`,
+/// and global git configuration are not read.
+#[derive(Debug, Default)]
+pub struct IgnoreFiles {
+    /// Rules per directory, `None` if the directory has no ignore files.
+    rules: HashMap<PathBuf, Option<Gitignore>>,
+}
+
+impl IgnoreFiles {
+    fn rules(&mut self, dir: &Path) -> Option<&Gitignore> {
+        self.rules
+            .entry(dir.to_owned())
+            .or_insert_with(|| {
+                let mut builder = GitignoreBuilder::new(dir);
+                let mut found = false;
+                for name in IGNORE_FILES.iter() {
+                    let path = dir.join(name);
+                    if path.is_file() {
+                        // invalid lines are skipped, valid rules in the same file still apply
+                        let _ = builder.add(path);
+                        found = true;
+                    }
+                }
+                found.then(|| builder.build().ok()).flatten()
+            })
+            .as_ref()
+    }
+
+    /// Returns `true` if `path` inside walked directory `root` is ignored.
+    pub fn is_ignored(&mut self, root: &Path, path: &Path, is_dir: bool) -> bool {
+        // closest directory first
+        for dir in path.ancestors().skip(1).take_while(|d| d.starts_with(root)) {
+            match self.rules(dir).map(|rules| rules.matched(path, is_dir)) {
+                Some(Match::Ignore(_)) => return true,
+                Some(Match::Whitelist(_)) => return false,
+                _ => (),
+            }
+        }
+        false
+    }
+}
+
+/// Compile paths for all files in `dir` recursively.
+/// Halts on errors, unless `options.ignore_errors` is set.
+/// Directories excluded via `options.exclude` or `options.exclude_regex` are not traversed.
+pub fn paths(dir: &Path, options: &WalkOptions) -> std::io::Result<Vec<PathBuf>> {
+    let (sender, receiver) = channel();
+    walk(dir, options, |path| {
+        let is_err = path.is_err();
+        sender.send(path).is_ok() && !is_err
+    })?;
+    drop(sender);
+    receiver.into_iter().collect()
+}
+
+/// Walks `dir` and calls `on_path` for each file, filtered as for `paths`, as soon as it is found.
+/// Errors, e.g. for unreadable directories, are also passed to `on_path` unless `options.ignore_errors`
+/// is set. Stops if `on_path` returns `false`.
+///
+/// If `options.parallel` is set, directories are walked in several threads
+/// with work stealing, and `on_path` is called from all of them in no particular order.
+/// This is faster for large trees on network file systems or with many small files,
+/// where a single thread waiting on directory listings is slower than hashing.
+pub fn walk<F>(dir: &Path, options: &WalkOptions, on_path: F) -> std::io::Result<()>
+where
+    F: Fn(std::io::Result<PathBuf>) -> bool + Sync,
+{
+    if options.parallel {
+        return walk_parallel(dir, options, on_path);
+    }
+    for path in paths_iter(dir, options)? {
+        if !on_path(path) {
+            break;
+        }
+    }
+    Ok(())
+}
+
+fn walk_parallel<F>(dir: &Path, options: &WalkOptions, on_path: F) -> std::io::Result<()>
+where
+    F: Fn(std::io::Result<PathBuf>) -> bool + Sync,
+{
+    let filter = PathFilter::new(options)?;
+    let relative_path = |path: &Path| path.strip_prefix(dir).unwrap_or(path).to_owned();
+
+    // Hidden files, extensions etc are filtered as for WalkDir via `include_path`,
+    // only ignore files are left to `ignore`
+    let mut builder = WalkBuilder::new(dir);
+    builder
+        .standard_filters(false)
+        .follow_links(options.follow_symlinks);
+    if options.use_ignore_files {
+        builder
+            .git_ignore(true)
+            .ignore(true)
+            .add_custom_ignore_filename(IGNORE_FILES[2])
+            .require_git(false);
+    }
+
+    builder.build_parallel().run(|| {
+        Box::new(|entry| {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(_) if options.ignore_errors => return WalkState::Continue,
+                Err(err) => {
+                    return match on_path(Err(std::io::Error::other(err))) {
+                        true => WalkState::Continue,
+                        false => WalkState::Quit,
+                    };
+                }
+            };
+            if entry.depth() == 0 {
+                return WalkState::Continue;
+            }
+            let path = entry.path();
+            if entry.file_type().is_some_and(|t| t.is_dir()) {
+                return match filter.is_excluded_dir(&relative_path(path)) {
+                    true => WalkState::Skip,
+                    false => WalkState::Continue,
+                };
+            }
+            // directories are pruned or walked above
+            if include_path(path, false, entry.path_is_symlink(), || entry.metadata().ok(), options)
+                && filter.is_included_file(&relative_path(path))
+                && !on_path(Ok(path.to_owned()))
+            {
+                return WalkState::Quit;
+            }
+            WalkState::Continue
+        })
+    });
+
+    Ok(())
+}
+
+/// Returns an iterator over paths for all files in `dir` recursively, filtered as for `paths`.
+/// Paths are yielded while the directory is being walked, e.g. to start hashing
+/// before the walk is done. Errors are yielded unless `options.ignore_errors` is set.
+pub fn paths_iter<'a>(
+    dir: &'a Path,
+    options: &'a WalkOptions,
+) -> std::io::Result<impl Iterator<Item = std::io::Result<PathBuf>> + Send + 'a> {
+    let filter = PathFilter::new(options)?;
+    let file_filter = filter.clone();
+    let mut ignore_files = IgnoreFiles::default();
+    let relative_path = move |entry: &DirEntry| entry.path().strip_prefix(dir).unwrap_or(entry.path()).to_owned();
+
+    Ok(WalkDir::new(dir)
+        // if follow symlinks = true, WalkDir must yield the followed path
+        // or chksum will attempt to open the symlink instead of the target
+        // path which will raise an error
+        .follow_links(options.follow_symlinks)
+        .into_iter()
+        // prune excluded and ignored directories, never the walked directory itself
+        .filter_entry(move |entry| {
+            let is_dir = entry.file_type().is_dir();
+            entry.depth() == 0
+                || !(is_dir && filter.is_excluded_dir(&relative_path(entry))
+                    || options.use_ignore_files && ignore_files.is_ignored(dir, entry.path(), is_dir))
+        })
+        .filter_map(move |direntry|
+            // Filter out dirs/dirnames/extensions/patterns
+            match direntry {
+                Ok(entry) => {
+                    if include(&entry, options) && file_filter.is_included_file(&relative_path(&entry)) {
+                        Some(Ok(entry.path().to_owned()))
+                    } else {
+                        None
+                    }
+                },
+                Err(e) => {
+                    // Whether to continue on error or not
+                    // E.g. when a socket is encountered
+                    match options.ignore_errors {
+                        true => None,
+                        false => Some(Err(e.into()))
+                    }
+                },
+            }
+        ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trailing_slash_matches_directories() {
+        let options = WalkOptions {
+            exclude: vec!["cache/".to_owned(), "/tmp/".to_owned()],
+            ..Default::default()
+        };
+        let filter = PathFilter::new(&options).unwrap();
+
+        assert!(filter.is_excluded_dir(Path::new("cache")));
+        assert!(filter.is_excluded_dir(Path::new("a/b/cache")));
+        assert!(filter.is_excluded_dir(Path::new("tmp")));
+        assert!(!filter.is_excluded_dir(Path::new("a/tmp")));
+        assert!(!filter.is_excluded_dir(Path::new("caches")));
+        // a file named like the directory is not excluded
+        assert!(filter.is_included_file(Path::new("a/cache")));
+        assert!(!filter.is_included_file(Path::new("a/cache/file.txt")));
+    }
+
+    #[test]
+    fn ignore_files_precedence() {
+        let root = std::env::temp_dir().join(format!("chksum_ignore_test_{}", std::process::id()));
+        std::fs::create_dir_all(root.join("sub")).unwrap();
+        std::fs::write(root.join(".gitignore"), "*.log\n*.tmp\n").unwrap();
+        // later ignore file in the same directory takes precedence
+        std::fs::write(root.join(".chksumignore"), "!keep.log\n").unwrap();
+        // closer directory takes precedence
+        std::fs::write(root.join("sub/.ignore"), "!sub.log\nsub.txt\n").unwrap();
+
+        let mut ignore_files = IgnoreFiles::default();
+        let mut is_ignored = |path: &str| ignore_files.is_ignored(&root, &root.join(path), false);
+        assert!(is_ignored("a.log"));
+        assert!(!is_ignored("keep.log"));
+        assert!(!is_ignored("a.txt"));
+        assert!(!is_ignored("sub/sub.log"));
+        assert!(is_ignored("sub/other.log"));
+        assert!(is_ignored("sub/sub.txt"));
+        assert!(is_ignored("sub/a.tmp"));
+        // rules in sub do not apply to its parent
+        assert!(!is_ignored("sub.txt"));
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}